@@ -0,0 +1,329 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Duration;
+
+enum Chunk {
+    /// A `RIFF` or `LIST` container, whose children follow directly.
+    List([u8; 4]),
+    /// Any other chunk, whose payload of `size` bytes follows directly.
+    Data { id: [u8; 4], size: u32 },
+}
+
+/// Reads the video frames of a Motion-JPEG AVI file.
+///
+/// Only the headers needed to size and pace the output are parsed; every `##dc`/`##db` chunk is
+/// handed out as a frame in file order, so the index at the end of the file is never consulted.
+pub struct AviReader<R> {
+    reader: R,
+    width: usize,
+    height: usize,
+    frame_interval: Duration,
+    /// Video chunks read so far, including the empty ones of dropped frames.
+    frames_read: u64,
+}
+
+impl AviReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> AviReader<R> {
+    /// Parses the file headers, leaving `reader` positioned at the first frame.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+        if &header[0..4] != b"RIFF" || &header[8..12] != b"AVI " {
+            return Err(invalid_data("not an AVI file"));
+        }
+
+        let mut avi = Self {
+            reader,
+            width: 0,
+            height: 0,
+            frame_interval: Duration::ZERO,
+            frames_read: 0,
+        };
+
+        let mut has_header = false;
+        loop {
+            match avi.next_chunk()? {
+                None => return Err(invalid_data("AVI file contains no movi list")),
+                Some(Chunk::List(kind)) if &kind == b"movi" => break,
+                Some(Chunk::List(_)) => {}
+                Some(Chunk::Data { id, size }) if &id == b"avih" && size >= 40 => {
+                    let mut avih = vec![0; size as usize];
+                    avi.read_payload(&mut avih)?;
+
+                    let field = |offset: usize| u32::from_le_bytes(avih[offset..offset + 4].try_into().unwrap());
+                    avi.frame_interval = Duration::from_micros(field(0) as u64);
+                    avi.width = field(32) as usize;
+                    avi.height = field(36) as usize;
+                    has_header = true;
+                }
+                Some(Chunk::Data { size, .. }) => avi.skip(size)?,
+            }
+        }
+
+        if !has_header {
+            return Err(invalid_data("AVI file contains no main header"));
+        }
+
+        Ok(avi)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Time between two frames as declared by the file, zero if the file does not say.
+    pub fn frame_interval(&self) -> Duration {
+        self.frame_interval
+    }
+
+    /// Overrides the frame interval, e.g. of files that do not declare one.
+    pub fn set_frame_interval(&mut self, interval: Duration) {
+        self.frame_interval = interval;
+    }
+
+    /// Number of the frame [`next_frame`](Self::next_frame) returned last, counting dropped
+    /// frames too.
+    pub fn sequence(&self) -> u64 {
+        self.frames_read.saturating_sub(1)
+    }
+
+    /// Returns the next compressed video frame, or `None` at the end of the file.
+    pub fn next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        while let Some(chunk) = self.next_chunk()? {
+            let Chunk::Data { id, size } = chunk else {
+                continue;
+            };
+
+            let video = &id[2..] == b"dc" || &id[2..] == b"db";
+            if video {
+                self.frames_read += 1;
+            }
+
+            // Empty video chunks mark dropped frames, everything else is audio, padding or index.
+            if size == 0 || !video {
+                self.skip(size)?;
+                continue;
+            }
+
+            let mut frame = vec![0; size as usize];
+            self.read_payload(&mut frame)?;
+            return Ok(Some(frame));
+        }
+
+        Ok(None)
+    }
+
+    /// Containers are not tracked, their children are simply walked as if they were siblings.
+    /// This also covers the `AVIX` extension lists that large OpenDML files append.
+    fn next_chunk(&mut self) -> io::Result<Option<Chunk>> {
+        let mut header = [0; 8];
+        match self.reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        let id = header[0..4].try_into().unwrap();
+        let size = u32::from_le_bytes(header[4..8].try_into().unwrap());
+
+        if &id == b"RIFF" || &id == b"LIST" {
+            let mut kind = [0; 4];
+            self.reader.read_exact(&mut kind)?;
+            return Ok(Some(Chunk::List(kind)));
+        }
+
+        Ok(Some(Chunk::Data { id, size }))
+    }
+
+    /// Reads a chunk payload along with the padding byte that keeps chunks word aligned.
+    fn read_payload(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.reader.read_exact(buf)?;
+        if buf.len() % 2 == 1 {
+            self.reader.seek(SeekFrom::Current(1))?;
+        }
+
+        Ok(())
+    }
+
+    fn skip(&mut self, size: u32) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Current(size as i64 + (size % 2) as i64))?;
+        Ok(())
+    }
+}
+
+/// Byte offsets of the header fields that are only known once all frames have been written.
+const RIFF_SIZE: u64 = 4;
+const AVIH_TOTAL_FRAMES: u64 = 48;
+const STRH_LENGTH: u64 = 140;
+const MOVI_SIZE: u64 = 216;
+/// Offset of the `movi` list type, which index entries are relative to.
+const MOVI_START: u64 = 220;
+const HEADER_LEN: u64 = 224;
+
+/// Writes a single-stream Motion-JPEG AVI file.
+///
+/// Frames are stored as they are handed in, so JPEG data coming straight from a camera is not
+/// re-encoded. The file is only valid once [`AviWriter::finish`] has filled in the header and
+/// written the index. Plain RIFF limits the file to 4 GiB.
+pub struct AviWriter<W: Write + Seek> {
+    writer: W,
+    width: usize,
+    height: usize,
+    /// Offset from `movi` and size of every frame chunk, for the `idx1` index.
+    index: Vec<(u32, u32)>,
+    pos: u64,
+}
+
+impl AviWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>, width: usize, height: usize, frame_interval: Duration) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), width, height, frame_interval)
+    }
+}
+
+impl<W: Write + Seek> AviWriter<W> {
+    pub fn new(mut writer: W, width: usize, height: usize, frame_interval: Duration) -> io::Result<Self> {
+        let micros = frame_interval.as_micros().clamp(1, u32::MAX as u128) as u32;
+        let (w, h) = (width as u32, height as u32);
+
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(b"RIFF\0\0\0\0AVI ");
+        header.extend_from_slice(b"LIST");
+        put_u32(&mut header, 192);
+        header.extend_from_slice(b"hdrl");
+
+        header.extend_from_slice(b"avih");
+        put_u32(&mut header, 56);
+        // Frame interval, max bytes per second, padding, flags (has index), total frames,
+        // initial frames, streams, suggested buffer size, width, height and four reserved words.
+        for value in [micros, 0, 0, 0x10, 0, 0, 1, w * h * 3, w, h, 0, 0, 0, 0] {
+            put_u32(&mut header, value);
+        }
+
+        header.extend_from_slice(b"LIST");
+        put_u32(&mut header, 116);
+        header.extend_from_slice(b"strl");
+
+        header.extend_from_slice(b"strh");
+        put_u32(&mut header, 56);
+        header.extend_from_slice(b"vidsMJPG");
+        // Flags, priority and language, initial frames, scale, rate, start, length, suggested
+        // buffer size, quality (default) and sample size.
+        for value in [0, 0, 0, micros, 1_000_000, 0, 0, w * h * 3, u32::MAX, 0] {
+            put_u32(&mut header, value);
+        }
+        // Destination rectangle as four 16-bit values.
+        put_u32(&mut header, 0);
+        put_u32(&mut header, (h << 16) | (w & 0xFFFF));
+
+        header.extend_from_slice(b"strf");
+        put_u32(&mut header, 40);
+        // BITMAPINFOHEADER: size, width, height, planes and bit count, compression, image size,
+        // resolution and palette fields.
+        for value in [40, w, h, (24 << 16) | 1] {
+            put_u32(&mut header, value);
+        }
+        header.extend_from_slice(b"MJPG");
+        for value in [w * h * 3, 0, 0, 0, 0] {
+            put_u32(&mut header, value);
+        }
+
+        header.extend_from_slice(b"LIST\0\0\0\0movi");
+        debug_assert_eq!(header.len() as u64, HEADER_LEN);
+
+        writer.write_all(&header)?;
+
+        Ok(Self {
+            writer,
+            width,
+            height,
+            index: Vec::new(),
+            pos: HEADER_LEN,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.index.len()
+    }
+
+    /// Appends an already encoded JPEG image as the next frame.
+    pub fn write_jpeg(&mut self, jpeg: &[u8]) -> io::Result<()> {
+        let size = u32::try_from(jpeg.len()).map_err(|_| invalid_data("frame is too large for AVI"))?;
+        let end = self.pos + 8 + jpeg.len() as u64 + jpeg.len() as u64 % 2;
+        if end + 16 * (self.index.len() as u64 + 1) + 8 > u32::MAX as u64 {
+            return Err(io::Error::new(ErrorKind::StorageFull, "AVI file would exceed 4 GiB"));
+        }
+
+        self.index.push(((self.pos - MOVI_START) as u32, size));
+
+        self.writer.write_all(b"00dc")?;
+        self.writer.write_all(&size.to_le_bytes())?;
+        self.writer.write_all(jpeg)?;
+        if jpeg.len() % 2 == 1 {
+            self.writer.write_all(&[0])?;
+        }
+
+        self.pos = end;
+        Ok(())
+    }
+
+    /// Writes the index, fills in the header fields that depend on the frame count and returns
+    /// the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let movi_size = (self.pos - MOVI_START) as u32;
+
+        let mut idx1 = Vec::with_capacity(8 + 16 * self.index.len());
+        idx1.extend_from_slice(b"idx1");
+        put_u32(&mut idx1, 16 * self.index.len() as u32);
+        for &(offset, size) in &self.index {
+            idx1.extend_from_slice(b"00dc");
+            // Every JPEG frame is a key frame.
+            put_u32(&mut idx1, 0x10);
+            put_u32(&mut idx1, offset);
+            put_u32(&mut idx1, size);
+        }
+
+        self.writer.write_all(&idx1)?;
+        let riff_size = (self.pos + idx1.len() as u64 - 8) as u32;
+        let frames = self.index.len() as u32;
+
+        for (offset, value) in [
+            (RIFF_SIZE, riff_size),
+            (AVIH_TOTAL_FRAMES, frames),
+            (STRH_LENGTH, frames),
+            (MOVI_SIZE, movi_size),
+        ] {
+            self.writer.seek(SeekFrom::Start(offset))?;
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,235 @@
+use std::str::FromStr;
+
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+
+/// Learning rate of background models given without one.
+pub const DEFAULT_LEARNING_RATE: f32 = 0.01;
+
+/// Gaussians per pixel of [`Background::Mixture`].
+const COMPONENTS: usize = 3;
+/// Variance of a new Gaussian, and the smallest a Gaussian can shrink to.
+const INITIAL_VARIANCE: f32 = 15.0 * 15.0;
+const MIN_VARIANCE: f32 = 4.0 * 4.0;
+/// Standard deviations within which a pixel matches a Gaussian.
+const MATCH_SIGMAS: f32 = 2.5;
+/// Share of the weight of a pixel's Gaussians that describes its background.
+const BACKGROUND_WEIGHT: f32 = 0.7;
+
+/// A per-pixel model of the still background that frames can be compared against instead of
+/// earlier frames, so that slow objects do not vanish while they move. Both models take a
+/// learning rate between `0` and `1`: how quickly anything that stops moving fades into the
+/// background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    /// Exponential running average of every channel.
+    Average(f32),
+    /// Mixture of Gaussians, which also learns backgrounds that flicker between a few colours,
+    /// like foliage or screens.
+    Mixture(f32),
+}
+
+impl Background {
+    pub fn learning_rate(&self) -> f32 {
+        match *self {
+            Background::Average(rate) | Background::Mixture(rate) => rate,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Background::Average(_) => "average",
+            Background::Mixture(_) => "mog",
+        }
+    }
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    /// Parses `average[:RATE]` or `mog[:RATE]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        let (model, rate) = s.split_once(':').unwrap_or((&s, ""));
+
+        let rate = if rate.is_empty() {
+            DEFAULT_LEARNING_RATE
+        } else {
+            match rate.parse::<f32>() {
+                Ok(rate) if rate > 0.0 && rate <= 1.0 => rate,
+                _ => return Err(format!("learning rate must be above 0 and at most 1, got {:?}", rate)),
+            }
+        };
+
+        match model {
+            "average" => Ok(Background::Average(rate)),
+            "mog" => Ok(Background::Mixture(rate)),
+            _ => Err(format!(
+                "unknown background model {:?}, expected average[:RATE] or mog[:RATE]",
+                model
+            )),
+        }
+    }
+}
+
+/// One Gaussian of a pixel's mixture.
+#[derive(Debug, Clone, Copy)]
+struct Component {
+    weight: f32,
+    mean: [f32; 3],
+    variance: f32,
+}
+
+impl Component {
+    /// How likely the component is to be background: heavy and narrow.
+    fn fitness(&self) -> f32 {
+        self.weight / self.variance.sqrt()
+    }
+}
+
+/// Learns a [`Background`] from a stream of `0RGB` frames.
+pub struct BackgroundModel {
+    background: Background,
+    /// Running average of every channel, for [`Background::Average`].
+    average: Vec<[f32; 3]>,
+    /// The Gaussians of every pixel from most to least likely background, for
+    /// [`Background::Mixture`].
+    mixtures: Vec<[Component; COMPONENTS]>,
+    reference: Vec<u32>,
+}
+
+impl BackgroundModel {
+    pub fn new(background: Background) -> Self {
+        Self {
+            background,
+            average: Vec::new(),
+            mixtures: Vec::new(),
+            reference: Vec::new(),
+        }
+    }
+
+    pub fn background(&self) -> Background {
+        self.background
+    }
+
+    /// The background the last frame was compared against.
+    pub fn reference(&self) -> &[u32] {
+        &self.reference
+    }
+
+    /// Returns the background to compare `frame` against, then learns from `frame`. The first
+    /// frame after creating the model becomes the background.
+    pub fn update(&mut self, frame: &[u32]) -> &[u32] {
+        if self.reference.len() != frame.len() {
+            self.reference = frame.to_vec();
+            self.average = frame.iter().map(|&pixel| channels(pixel)).collect();
+            self.mixtures = self
+                .average
+                .iter()
+                .map(|&mean| {
+                    let mut mixture = [Component {
+                        weight: 0.0,
+                        mean,
+                        variance: INITIAL_VARIANCE,
+                    }; COMPONENTS];
+                    mixture[0].weight = 1.0;
+                    mixture
+                })
+                .collect();
+            return &self.reference;
+        }
+
+        match self.background {
+            Background::Average(rate) => {
+                self.reference
+                    .par_iter_mut()
+                    .zip(&mut self.average)
+                    .zip(frame)
+                    .for_each(|((reference, average), &pixel)| {
+                        *reference = pack(*average);
+                        for (average, channel) in average.iter_mut().zip(channels(pixel)) {
+                            *average += (channel - *average) * rate;
+                        }
+                    });
+            }
+            Background::Mixture(rate) => {
+                self.reference
+                    .par_iter_mut()
+                    .zip(&mut self.mixtures)
+                    .zip(frame)
+                    .for_each(|((reference, mixture), &pixel)| {
+                        *reference = update_mixture(mixture, channels(pixel), rate);
+                    });
+            }
+        }
+
+        &self.reference
+    }
+}
+
+/// Returns the background a pixel of value `x` is compared against and learns `x` into the
+/// pixel's `mixture`, after Stauffer and Grimson.
+fn update_mixture(mixture: &mut [Component; COMPONENTS], x: [f32; 3], rate: f32) -> u32 {
+    let distance = |mean: [f32; 3]| -> f32 { mean.iter().zip(x).map(|(m, x)| (x - m) * (x - m)).sum() };
+
+    let matched = mixture.iter().position(|component| {
+        component.weight > 0.0 && distance(component.mean) < MATCH_SIGMAS * MATCH_SIGMAS * 3.0 * component.variance
+    });
+
+    // The most likely components that make up the background weight.
+    let mut total = 0.0;
+    let backgrounds = mixture
+        .iter()
+        .take_while(|component| {
+            let below = total < BACKGROUND_WEIGHT;
+            total += component.weight;
+            below
+        })
+        .count();
+
+    // Values matching a background component are compared against it and so show little change,
+    // anything else against the most likely background.
+    let reference = match matched {
+        Some(k) if k < backgrounds => pack(mixture[k].mean),
+        _ => pack(mixture[0].mean),
+    };
+
+    for component in mixture.iter_mut() {
+        component.weight *= 1.0 - rate;
+    }
+
+    match matched {
+        Some(k) => {
+            let component = &mut mixture[k];
+            component.weight += rate;
+            let learn = (rate / component.weight).min(1.0);
+            let squared = distance(component.mean) / 3.0;
+            for (mean, x) in component.mean.iter_mut().zip(x) {
+                *mean += (x - *mean) * learn;
+            }
+            component.variance = (component.variance + (squared - component.variance) * learn).max(MIN_VARIANCE);
+        }
+        None => {
+            mixture[COMPONENTS - 1] = Component {
+                weight: rate,
+                mean: x,
+                variance: INITIAL_VARIANCE,
+            };
+            let total: f32 = mixture.iter().map(|component| component.weight).sum();
+            for component in mixture.iter_mut() {
+                component.weight /= total;
+            }
+        }
+    }
+
+    mixture.sort_by(|a, b| b.fitness().total_cmp(&a.fitness()));
+    reference
+}
+
+fn channels(pixel: u32) -> [f32; 3] {
+    [(pixel >> 16) & 0xFF, (pixel >> 8) & 0xFF, pixel & 0xFF].map(|c| c as f32)
+}
+
+fn pack(channels: [f32; 3]) -> u32 {
+    let [r, g, b] = channels.map(|c| c.round().clamp(0.0, 255.0) as u32);
+    (r << 16) | (g << 8) | b
+}
@@ -0,0 +1,444 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+use motion_extraction::{
+    AutoControl, Background, CaptureConfig, ChannelDelays, ChannelModes, Colormap, DelayUnit, DetectorConfig, DiffMode,
+    EventConfig, ImageFormat, Pattern, PowerLine, Prefilter, RoiMask, ScoreKind, Smoothing, parse_polygons,
+};
+
+use crate::controls::KEY_HELP;
+
+pub const MAX_DELAY: i64 = 300;
+pub const MAX_DELAY_MS: i64 = 10_000;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Real-time motion extraction from a V4L2 camera", after_help = KEY_HELP)]
+pub struct Args {
+    /// Path of the V4L2 capture device. May be given several times to capture from several
+    /// cameras at once, which are shown side by side
+    #[arg(short, long, default_value = "/dev/video0")]
+    pub device: Vec<String>,
+
+    /// List the V4L2 devices and the pixel formats they offer, then exit
+    #[arg(long)]
+    pub list_devices: bool,
+
+    /// List the resolutions and frame rates the device offers in each pixel format, then exit
+    #[arg(long, conflicts_with = "list_devices")]
+    pub list_modes: bool,
+
+    /// List the controls of the device, like exposure and white balance, with their current
+    /// values, then exit
+    #[arg(long, conflicts_with_all = ["list_devices", "list_modes"])]
+    pub list_controls: bool,
+
+    /// Print the value of a control of the device, then exit. May be given several times
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["list_devices", "list_modes", "list_controls"])]
+    pub get_control: Vec<String>,
+
+    /// Set a control of the device, by the name --list-controls shows, to a number or menu item.
+    /// May be given several times. Changed controls are restored when the program exits
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_assignment)]
+    pub control: Vec<(String, String)>,
+
+    /// Stop the camera from adjusting these settings by itself, which keeps their current values
+    /// [possible values: exposure, white-balance, focus, gain]. Set values of their own with
+    /// --control, e.g. --control exposure_time_absolute=200
+    #[arg(long, value_delimiter = ',')]
+    pub lock: Vec<AutoControl>,
+
+    /// Frequency of the mains power, for the camera to filter out the flicker of lights
+    /// [possible values: off, 50, 60, auto]
+    #[arg(long)]
+    pub power_line: Option<PowerLine>,
+
+    /// Stop when the camera fails instead of trying to reopen it until it comes back
+    #[arg(long)]
+    pub no_reconnect: bool,
+
+    /// Play back a Motion-JPEG AVI file or a directory of JPEG/PNG images instead of the camera
+    #[arg(short, long)]
+    pub input: Option<PathBuf>,
+
+    /// Generate a test pattern instead of using the camera [possible values: squares, bar, noise]
+    #[arg(long, conflicts_with = "input")]
+    pub pattern: Option<Pattern>,
+
+    /// Seed of the noise test pattern
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Feed file input and test patterns as fast as they can be processed instead of at their
+    /// frame rate
+    #[arg(long)]
+    pub no_pacing: bool,
+
+    /// Write the processed frames to a Motion-JPEG AVI file if the path ends in `.avi`, or to a
+    /// directory of numbered images otherwise
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Image format of directory output [possible values: png, ppm]
+    #[arg(long, default_value = "png")]
+    pub image_format: ImageFormat,
+
+    /// JPEG quality of AVI output
+    #[arg(long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub jpeg_quality: u8,
+
+    /// Do not open a window, e.g. to batch-process recordings with --input and --output
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Report every few seconds how long frames take in each stage of the pipeline, how many
+    /// wait in its queues, how many were dropped and how long after capture they are shown. The
+    /// report goes to stderr and to the on-screen display
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Capture width in pixels
+    #[arg(long, default_value_t = 1280, value_parser = parse_dimension)]
+    pub width: usize,
+
+    /// Capture height in pixels
+    #[arg(long, default_value_t = 720, value_parser = parse_dimension)]
+    pub height: usize,
+
+    /// Frame interval as frames per second, i.e. an interval of 1/FPS. Also paces image sequences
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    pub fps: u32,
+
+    /// Pixel format requested from the camera as a FourCC code (MJPG, YUYV, NV12, RGB3 or GREY).
+    /// Negotiated with the camera if omitted, preferring raw formats that need no JPEG decoding
+    #[arg(long, value_parser = parse_fourcc)]
+    pub format: Option<[u8; 4]>,
+
+    /// Frames, or milliseconds with --delay-unit ms, between the output frame and the frame its
+    /// red channel is compared against. Negative values compare against later frames by delaying
+    /// the output
+    #[arg(long, default_value_t = 1, allow_negative_numbers = true, value_parser = delay_parser())]
+    pub red_delay: i32,
+
+    /// Frames between the output frame and the frame its green channel is compared against
+    #[arg(long, default_value_t = 5, allow_negative_numbers = true, value_parser = delay_parser())]
+    pub green_delay: i32,
+
+    /// Frames between the output frame and the frame its blue channel is compared against
+    #[arg(long, default_value_t = 9, allow_negative_numbers = true, value_parser = delay_parser())]
+    pub blue_delay: i32,
+
+    /// What the delays count [possible values: frames, ms]. Delays in milliseconds are measured
+    /// between capture timestamps, so the effect does not change when the camera drops frames or
+    /// changes its frame rate
+    #[arg(long, default_value = "frames")]
+    pub delay_unit: DelayUnit,
+
+    /// How each channel is compared against the older frame [possible values: saturating,
+    /// absolute, signed]. Saturating only shows what got brighter, absolute also shows what got
+    /// darker and signed shows both around mid-grey
+    #[arg(long, default_value = "saturating")]
+    pub diff_mode: DiffMode,
+
+    /// Diff mode of the red channel, overriding --diff-mode
+    #[arg(long)]
+    pub red_diff_mode: Option<DiffMode>,
+
+    /// Diff mode of the green channel, overriding --diff-mode
+    #[arg(long)]
+    pub green_diff_mode: Option<DiffMode>,
+
+    /// Diff mode of the blue channel, overriding --diff-mode
+    #[arg(long)]
+    pub blue_diff_mode: Option<DiffMode>,
+
+    /// Diff the luma of frames instead of each colour channel and show it through --colormap
+    #[arg(long)]
+    pub luma: bool,
+
+    /// Frames between the output frame and the frame its luma is compared against
+    #[arg(long, default_value_t = 1, allow_negative_numbers = true, value_parser = delay_parser())]
+    pub luma_delay: i32,
+
+    /// Colour map of luma motion [possible values: heat, viridis, grayscale, false-colour]
+    #[arg(long, default_value = "heat")]
+    pub colormap: Colormap,
+
+    /// Diff frames against a learned background instead of earlier frames, which keeps slow
+    /// objects visible [possible values: average[:RATE], mog[:RATE]]. The learning rate, 0.01 by
+    /// default, is how quickly things that stop moving fade into the background. Also available
+    /// with the G key
+    #[arg(long)]
+    pub background: Option<Background>,
+
+    /// Leave trails of motion like a long exposure, which fade by this factor, between 0 and 1,
+    /// every frame. E.g. 0.95 keeps a trail visible for a few seconds. Cleared with the X key
+    #[arg(long, value_parser = parse_decay)]
+    pub trails: Option<f32>,
+
+    /// Treat changes of a pixel below this, out of 255, as sensor noise
+    #[arg(long)]
+    pub noise_threshold: Option<u8>,
+
+    /// Learn the noise floor of every pixel over this many seconds at the start, which should
+    /// show a still scene. Also available with the N key
+    #[arg(long, value_parser = parse_seconds)]
+    pub calibrate: Option<f64>,
+
+    /// Average the output over consecutive frames [possible values: off, ema:WEIGHT, box:FRAMES].
+    /// E.g. ema:0.3 weights every new frame by 0.3, box:4 shows the mean of the last 4 frames
+    #[arg(long, default_value = "off")]
+    pub smooth: Smoothing,
+
+    /// Filter frames before diffing them [possible values: off, gaussian:SIGMA, box:RADIUS,
+    /// downscale:FACTOR]. Blurring hides camera shake and compression noise, downscale:2 diffs
+    /// at quarter resolution, which needs far less processing
+    #[arg(long, default_value = "off")]
+    pub prefilter: Prefilter,
+
+    /// Only extract motion inside a region of interest, read from a PNG image (bright pixels are
+    /// inside) or from a text file with one polygon per line, written as `x,y x,y x,y ...`
+    #[arg(long)]
+    pub roi: Option<PathBuf>,
+
+    /// Ignore motion inside the region given with --roi instead of outside it
+    #[arg(long, requires = "roi")]
+    pub invert_roi: bool,
+
+    /// Detect moving objects and outline them in the window
+    #[arg(long)]
+    pub detect: bool,
+
+    /// Smallest change of a pixel, out of 255, that counts as motion when detecting objects or
+    /// scoring motion
+    #[arg(long, default_value_t = 32)]
+    pub threshold: u8,
+
+    /// Erosion passes that remove noise from the motion mask
+    #[arg(long, default_value_t = 1)]
+    pub erode: usize,
+
+    /// Dilation passes that join up the parts of moving objects after eroding
+    #[arg(long, default_value_t = 2)]
+    pub dilate: usize,
+
+    /// Smallest number of pixels a moving object is made of
+    #[arg(long, default_value_t = 16)]
+    pub min_area: usize,
+
+    /// Append periods of motion to this file as JSON lines. The options below also decide when
+    /// --clips are recorded
+    #[arg(long)]
+    pub events: Option<PathBuf>,
+
+    /// Motion score of a frame [possible values: changed, energy]. Changed is the fraction of
+    /// pixels that changed by at least --threshold, energy the mean change of all pixels
+    #[arg(long, default_value = "changed")]
+    pub score: ScoreKind,
+
+    /// Motion score, from 0 to 1, at which a motion event starts
+    #[arg(long, default_value_t = 0.01)]
+    pub trigger: f64,
+
+    /// Motion score, from 0 to 1, below which a motion event ends
+    #[arg(long, default_value_t = 0.005)]
+    pub release: f64,
+
+    /// Seconds the score has to stay above --trigger for a motion event to start
+    #[arg(long, default_value_t = 0.2, value_parser = parse_seconds)]
+    pub min_event: f64,
+
+    /// Seconds the score has to stay below --release for a motion event to end
+    #[arg(long, default_value_t = 1.0, value_parser = parse_seconds)]
+    pub release_after: f64,
+
+    /// Save Motion-JPEG AVI clips of periods of motion into this directory
+    #[arg(long)]
+    pub clips: Option<PathBuf>,
+
+    /// Seconds of footage before the motion started that clips begin with
+    #[arg(long, default_value_t = 3.0, value_parser = parse_seconds)]
+    pub pre_roll: f64,
+
+    /// Seconds of footage after the motion stopped that clips end with
+    #[arg(long, default_value_t = 3.0, value_parser = parse_seconds)]
+    pub post_roll: f64,
+
+    /// Start with the on-screen display of the current settings shown
+    #[arg(long)]
+    pub hud: bool,
+
+    /// Window title, derived from the resolution and frame rate if omitted
+    #[arg(long)]
+    pub title: Option<String>,
+}
+
+impl Args {
+    pub fn title(&self, config: &CaptureConfig) -> String {
+        match &self.title {
+            Some(title) => title.clone(),
+            None => format!(
+                "Motion Extraction ({}p{})",
+                config.resolution.1,
+                config.interval.1 / config.interval.0.max(1)
+            ),
+        }
+    }
+
+    pub fn capture_config(&self) -> CaptureConfig {
+        CaptureConfig {
+            resolution: (self.width, self.height),
+            interval: (1, self.fps),
+            format: self.format,
+        }
+    }
+
+    /// Largest delay in the unit of the delays. Keeps the frame history within a few hundred
+    /// frames, as every frame of it stays in memory.
+    pub fn max_delay(&self) -> i32 {
+        match self.delay_unit {
+            DelayUnit::Frames => MAX_DELAY as i32,
+            DelayUnit::Milliseconds => MAX_DELAY_MS as i32,
+        }
+    }
+
+    /// Checks what clap can not check on its own.
+    pub fn check(&self) -> Result<(), String> {
+        let max = self.max_delay();
+        for delay in [self.red_delay, self.green_delay, self.blue_delay, self.luma_delay] {
+            if delay.abs() > max {
+                return Err(format!(
+                    "delay {} is out of range, delays in {} are at most {}",
+                    delay,
+                    self.delay_unit.name(),
+                    max
+                ));
+            }
+        }
+
+        if self.release > self.trigger {
+            return Err(format!(
+                "--release {} is above --trigger {}, events would never end",
+                self.release, self.trigger
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn delays(&self) -> ChannelDelays {
+        ChannelDelays::new(self.red_delay, self.green_delay, self.blue_delay)
+    }
+
+    pub fn detector_config(&self) -> DetectorConfig {
+        DetectorConfig {
+            threshold: self.threshold,
+            erode: self.erode,
+            dilate: self.dilate,
+            min_area: self.min_area,
+        }
+    }
+
+    pub fn event_config(&self) -> EventConfig {
+        EventConfig {
+            trigger: self.trigger,
+            release: self.release,
+            min_duration: Duration::from_secs_f64(self.min_event),
+            release_after: Duration::from_secs_f64(self.release_after),
+        }
+    }
+
+    pub fn pre_roll(&self) -> Duration {
+        Duration::from_secs_f64(self.pre_roll)
+    }
+
+    pub fn post_roll(&self) -> Duration {
+        Duration::from_secs_f64(self.post_roll)
+    }
+
+    /// Loads the region of interest, if one was given, for frames of `width` by `height` pixels
+    /// that were captured at `capture_size`. Polygons are given in captured pixels.
+    pub fn roi(&self, capture_size: (usize, usize), (width, height): (usize, usize)) -> io::Result<Option<RoiMask>> {
+        let Some(path) = &self.roi else {
+            return Ok(None);
+        };
+
+        let is_png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        let mut roi = if is_png {
+            RoiMask::from_png(path, width, height)?
+        } else {
+            let text = fs::read_to_string(path)?;
+            let mut polygons = parse_polygons(&text).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+
+            let scale = (
+                width as f64 / capture_size.0 as f64,
+                height as f64 / capture_size.1 as f64,
+            );
+            for (x, y) in polygons.iter_mut().flatten() {
+                (*x, *y) = (*x * scale.0, *y * scale.1);
+            }
+
+            RoiMask::from_polygons(&polygons, width, height)
+        };
+
+        if self.invert_roi {
+            roi.invert();
+        }
+
+        Ok(Some(roi))
+    }
+
+    pub fn modes(&self) -> ChannelModes {
+        ChannelModes::new(
+            self.red_diff_mode.unwrap_or(self.diff_mode),
+            self.green_diff_mode.unwrap_or(self.diff_mode),
+            self.blue_diff_mode.unwrap_or(self.diff_mode),
+        )
+    }
+}
+
+/// Delays in frames are held to [`MAX_DELAY`] by [`Args::check`].
+fn delay_parser() -> clap::builder::RangedI64ValueParser<i32> {
+    clap::value_parser!(i32).range(-MAX_DELAY_MS..=MAX_DELAY_MS)
+}
+
+fn parse_fourcc(s: &str) -> Result<[u8; 4], String> {
+    s.as_bytes()
+        .try_into()
+        .map_err(|_| format!("expected a four character code, got {:?}", s))
+}
+
+fn parse_assignment(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), value.trim().to_string())),
+        _ => Err(format!("expected NAME=VALUE, got {:?}", s)),
+    }
+}
+
+fn parse_dimension(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(pixels) if pixels > 0 => Ok(pixels),
+        _ => Err(format!("expected a positive number of pixels, got {:?}", s)),
+    }
+}
+
+fn parse_decay(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(decay) if (0.0..1.0).contains(&decay) => Ok(decay),
+        _ => Err(format!(
+            "expected a decay factor of at least 0 and below 1, got {:?}",
+            s
+        )),
+    }
+}
+
+fn parse_seconds(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(seconds),
+        _ => Err(format!("expected a non-negative number of seconds, got {:?}", s)),
+    }
+}
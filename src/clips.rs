@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::avi::AviWriter;
+use crate::events;
+
+/// Saves Motion-JPEG AVI clips of the frames around periods of motion.
+///
+/// The last few seconds of frames are kept as JPEG data, so a clip can start some time before the
+/// motion was noticed (the pre-roll) and go on for a while after it stopped (the post-roll).
+pub struct ClipRecorder {
+    dir: PathBuf,
+    width: usize,
+    height: usize,
+    frame_interval: Duration,
+    pre_roll: usize,
+    post_roll: usize,
+    history: VecDeque<Vec<u8>>,
+    clip: Option<Clip>,
+}
+
+struct Clip {
+    writer: AviWriter<BufWriter<File>>,
+    path: PathBuf,
+    /// Frames left to write once the motion has stopped.
+    remaining: usize,
+}
+
+impl ClipRecorder {
+    /// Creates `dir` if it does not exist yet. The pre- and post-roll are rounded up to whole
+    /// frames of `frame_interval`.
+    pub fn create(
+        dir: impl AsRef<Path>,
+        (width, height): (usize, usize),
+        frame_interval: Duration,
+        pre_roll: Duration,
+        post_roll: Duration,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        let frames =
+            |duration: Duration| (duration.as_secs_f64() / frame_interval.as_secs_f64().max(1e-6)).ceil() as usize;
+
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            width,
+            height,
+            frame_interval,
+            pre_roll: frames(pre_roll),
+            post_roll: frames(post_roll),
+            history: VecDeque::new(),
+            clip: None,
+        })
+    }
+
+    /// Whether a clip is being written.
+    pub fn is_recording(&self) -> bool {
+        self.clip.is_some()
+    }
+
+    /// Hands in the next frame as JPEG data, along with whether there is motion in it and when it
+    /// was captured. Motion starts a clip named after `time`, which begins with the pre-roll.
+    ///
+    /// Returns the path of the clip that was completed with this frame, if any.
+    pub fn push_frame(&mut self, jpeg: Vec<u8>, motion: bool, time: SystemTime) -> io::Result<Option<PathBuf>> {
+        let Some(clip) = &mut self.clip else {
+            if !motion {
+                self.remember(jpeg);
+                return Ok(None);
+            }
+
+            let name = format!("motion-{}.avi", events::format_utc(time).replace(':', "-"));
+            let path = self.dir.join(name);
+            let mut writer = AviWriter::create(&path, self.width, self.height, self.frame_interval)?;
+
+            for frame in self.history.drain(..) {
+                writer.write_jpeg(&frame)?;
+            }
+            writer.write_jpeg(&jpeg)?;
+
+            self.clip = Some(Clip {
+                writer,
+                path,
+                remaining: self.post_roll,
+            });
+
+            return Ok(None);
+        };
+
+        if motion {
+            clip.remaining = self.post_roll;
+        } else if clip.remaining > 0 {
+            clip.remaining -= 1;
+        } else {
+            // The frame after the post-roll is the first one of the next clip's pre-roll.
+            let path = self.finish()?;
+            self.remember(jpeg);
+            return Ok(path);
+        }
+
+        clip.writer.write_jpeg(&jpeg)?;
+        Ok(None)
+    }
+
+    fn remember(&mut self, jpeg: Vec<u8>) {
+        self.history.push_back(jpeg);
+        while self.history.len() > self.pre_roll {
+            self.history.pop_front();
+        }
+    }
+
+    /// Completes the clip being written, if any, and returns its path.
+    pub fn finish(&mut self) -> io::Result<Option<PathBuf>> {
+        match self.clip.take() {
+            Some(clip) => {
+                clip.writer.finish()?;
+                Ok(Some(clip.path))
+            }
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::str::FromStr;
+
+/// Maps single-channel intensities onto `0RGB` colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Colormap {
+    /// Black through red and yellow to white.
+    #[default]
+    Heat,
+    /// The perceptually uniform purple, teal and yellow map, readable in greyscale print too.
+    Viridis,
+    Grayscale,
+    /// Black through the rainbow from blue to red, then white, to tell levels apart at a glance.
+    FalseColour,
+}
+
+const HEAT: [u32; 4] = [0x000000, 0xFF0000, 0xFFFF00, 0xFFFFFF];
+const VIRIDIS: [u32; 5] = [0x440154, 0x3B528B, 0x21918C, 0x5EC962, 0xFDE725];
+const GRAYSCALE: [u32; 2] = [0x000000, 0xFFFFFF];
+const FALSE_COLOUR: [u32; 7] = [0x000000, 0x0000FF, 0x00FFFF, 0x00FF00, 0xFFFF00, 0xFF0000, 0xFFFFFF];
+
+impl Colormap {
+    /// The next colour map in the order they are listed in, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            Colormap::Heat => Colormap::Viridis,
+            Colormap::Viridis => Colormap::Grayscale,
+            Colormap::Grayscale => Colormap::FalseColour,
+            Colormap::FalseColour => Colormap::Heat,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::Heat => "heat",
+            Colormap::Viridis => "viridis",
+            Colormap::Grayscale => "grayscale",
+            Colormap::FalseColour => "false-colour",
+        }
+    }
+
+    /// The colour of every intensity from `0` to `255`.
+    pub fn palette(self) -> [u32; 256] {
+        let stops: &[u32] = match self {
+            Colormap::Heat => &HEAT,
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Grayscale => &GRAYSCALE,
+            Colormap::FalseColour => &FALSE_COLOUR,
+        };
+
+        std::array::from_fn(|value| gradient(stops, value as u32))
+    }
+}
+
+impl FromStr for Colormap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "heat" => Ok(Colormap::Heat),
+            "viridis" => Ok(Colormap::Viridis),
+            "grayscale" | "greyscale" | "gray" | "grey" => Ok(Colormap::Grayscale),
+            "false-colour" | "false-color" | "false" => Ok(Colormap::FalseColour),
+            _ => Err(format!(
+                "unknown colour map {:?}, expected heat, viridis, grayscale or false-colour",
+                s
+            )),
+        }
+    }
+}
+
+/// Linearly interpolates between equally spaced colour `stops` at `value` out of `255`.
+fn gradient(stops: &[u32], value: u32) -> u32 {
+    let segments = stops.len() as u32 - 1;
+    let position = value * segments;
+    let index = (position / 255).min(segments - 1) as usize;
+    let t = position - index as u32 * 255;
+
+    let (from, to) = (stops[index], stops[index + 1]);
+    [16, 8, 0].iter().fold(0, |colour, &shift| {
+        let a = (from >> shift) & 0xFF;
+        let b = (to >> shift) & 0xFF;
+        colour | (((a * (255 - t) + b * t + 127) / 255) << shift)
+    })
+}
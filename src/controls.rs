@@ -0,0 +1,306 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use minifb::{Key, KeyRepeat, Window};
+use motion_extraction::{
+    Background, ChannelDelays, ChannelModes, Colormap, DEFAULT_LEARNING_RATE, DelayUnit, MotionExtractor, MotionMode,
+};
+
+use crate::cli::Args;
+
+pub const KEY_HELP: &str = "\
+Keys:
+  Q / A    Increase / decrease the red delay
+  W / S    Increase / decrease the green delay
+  E / D    Increase / decrease the blue delay
+  U / J    Increase / decrease the luma delay
+  R        Reset the effect settings
+  T        Cycle the diff mode of all channels
+  L        Switch between colour and luma motion
+  C        Cycle the colour map of luma motion
+  G        Switch between diffing against earlier frames and the background
+  N        Learn the noise floor, keep the scene still meanwhile
+  X        Clear the motion trails
+  B        Toggle outlining moving objects
+  M        Toggle mirroring
+  Space    Freeze / unfreeze the picture
+  H        Show / hide the on-screen display
+  1 - 9    Select the camera the keys above change the effect of
+  F        Show the selected camera alone / all cameras
+  F11      Toggle fullscreen
+  Click    Add a corner to an area to ignore, right click or Enter closes it. Only while
+           a single camera is shown
+  Backspace  Remove the last corner, or the areas drawn so far
+  Escape   Quit";
+
+/// Settings of the effect itself, applied to the [`MotionExtractor`] whenever they change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Effect {
+    pub delays: ChannelDelays,
+    pub modes: ChannelModes,
+    pub luma: bool,
+    pub luma_delay: i32,
+    pub colormap: Colormap,
+    pub background: Option<Background>,
+}
+
+impl Effect {
+    pub fn mode(&self) -> MotionMode {
+        if self.luma {
+            MotionMode::Luma {
+                delay: self.luma_delay,
+                colormap: self.colormap,
+            }
+        } else {
+            MotionMode::Rgb
+        }
+    }
+
+    pub fn apply(&self, extractor: &mut MotionExtractor) {
+        extractor.set_delays(self.delays);
+        extractor.set_modes(self.modes);
+        extractor.set_mode(self.mode());
+        extractor.set_background(self.background);
+    }
+}
+
+/// Settings that can be changed with the keyboard while the program runs.
+pub struct Controls {
+    /// The effect of every camera.
+    pub effects: Vec<Effect>,
+    initial_effect: Effect,
+    /// The camera the effect keys apply to.
+    pub selected: usize,
+    /// Whether the selected camera is shown alone rather than all cameras in a grid.
+    pub full_window: bool,
+    /// The background model the G key switches to.
+    background: Background,
+    delay_unit: DelayUnit,
+    /// How much a key press changes a delay by, one frame's worth in either unit.
+    delay_step: i32,
+    max_delay: i32,
+    /// Shared with the decode thread, which does the mirroring.
+    pub mirror: Arc<AtomicBool>,
+    pub frozen: bool,
+    /// Set when noise floor calibration was asked for, until it has been started.
+    pub calibrate: bool,
+    /// Set when clearing the motion trails was asked for, until they have been cleared.
+    pub clear_trails: bool,
+    pub detect: bool,
+    pub show_hud: bool,
+}
+
+impl Controls {
+    pub fn new(args: &Args, mirror: bool, cameras: usize) -> Self {
+        let effect = Effect {
+            delays: args.delays(),
+            modes: args.modes(),
+            luma: args.luma,
+            luma_delay: args.luma_delay,
+            colormap: args.colormap,
+            background: args.background,
+        };
+
+        Self {
+            effects: vec![effect; cameras],
+            initial_effect: effect,
+            selected: 0,
+            full_window: false,
+            background: args.background.unwrap_or(Background::Average(DEFAULT_LEARNING_RATE)),
+            delay_unit: args.delay_unit,
+            delay_step: match args.delay_unit {
+                DelayUnit::Frames => 1,
+                DelayUnit::Milliseconds => (1000 / args.fps.max(1)).max(1) as i32,
+            },
+            max_delay: args.max_delay(),
+            mirror: Arc::new(AtomicBool::new(mirror)),
+            frozen: false,
+            calibrate: false,
+            clear_trails: false,
+            detect: args.detect,
+            show_hud: args.hud,
+        }
+    }
+
+    /// Whether a single camera fills the window.
+    pub fn shows_one(&self) -> bool {
+        self.effects.len() == 1 || self.full_window
+    }
+
+    /// Applies the keys pressed since the last call. Returns whether the effect of the selected
+    /// camera changed.
+    pub fn update(&mut self, window: &Window) -> bool {
+        let digits = [
+            Key::Key1,
+            Key::Key2,
+            Key::Key3,
+            Key::Key4,
+            Key::Key5,
+            Key::Key6,
+            Key::Key7,
+            Key::Key8,
+            Key::Key9,
+        ];
+        for (camera, digit) in digits.into_iter().enumerate().take(self.effects.len()) {
+            if window.is_key_pressed(digit, KeyRepeat::No) {
+                self.selected = camera;
+            }
+        }
+
+        if window.is_key_pressed(Key::F, KeyRepeat::No) {
+            self.full_window = !self.full_window;
+        }
+
+        let previous = self.effects[self.selected];
+        let effect = &mut self.effects[self.selected];
+
+        let (delay_step, max_delay) = (self.delay_step, self.max_delay);
+        let step = |up: Key, down: Key, delay: &mut i32| {
+            if window.is_key_pressed(up, KeyRepeat::Yes) {
+                *delay = (*delay + delay_step).min(max_delay);
+            }
+            if window.is_key_pressed(down, KeyRepeat::Yes) {
+                *delay = (*delay - delay_step).max(-max_delay);
+            }
+        };
+
+        step(Key::Q, Key::A, &mut effect.delays.red);
+        step(Key::W, Key::S, &mut effect.delays.green);
+        step(Key::E, Key::D, &mut effect.delays.blue);
+        step(Key::U, Key::J, &mut effect.luma_delay);
+
+        if window.is_key_pressed(Key::R, KeyRepeat::No) {
+            *effect = self.initial_effect;
+        }
+
+        // Mixed modes are brought in line by cycling from the red channel's mode.
+        if window.is_key_pressed(Key::T, KeyRepeat::No) {
+            effect.modes = ChannelModes::all(effect.modes.red.next());
+        }
+
+        if window.is_key_pressed(Key::L, KeyRepeat::No) {
+            effect.luma = !effect.luma;
+        }
+
+        if window.is_key_pressed(Key::C, KeyRepeat::No) {
+            effect.colormap = effect.colormap.next();
+        }
+
+        if window.is_key_pressed(Key::G, KeyRepeat::No) {
+            effect.background = match effect.background {
+                Some(_) => None,
+                None => Some(self.background),
+            };
+        }
+
+        if window.is_key_pressed(Key::N, KeyRepeat::No) {
+            self.calibrate = true;
+        }
+
+        if window.is_key_pressed(Key::X, KeyRepeat::No) {
+            self.clear_trails = true;
+        }
+
+        if window.is_key_pressed(Key::B, KeyRepeat::No) {
+            self.detect = !self.detect;
+        }
+
+        if window.is_key_pressed(Key::M, KeyRepeat::No) {
+            self.mirror.fetch_xor(true, Ordering::Relaxed);
+        }
+
+        if window.is_key_pressed(Key::Space, KeyRepeat::No) {
+            self.frozen = !self.frozen;
+        }
+
+        if window.is_key_pressed(Key::H, KeyRepeat::No) {
+            self.show_hud = !self.show_hud;
+        }
+
+        self.effects[self.selected] != previous
+    }
+
+    /// Describes the settings of `camera`. `blobs` is the number of moving objects, if they are
+    /// being detected, `motion` the motion score and whether a motion event is in progress, if
+    /// events are being logged, and `outages` the number of times the camera was lost.
+    pub fn hud_lines(
+        &self,
+        camera: usize,
+        fps: f64,
+        extractor: &MotionExtractor,
+        blobs: Option<usize>,
+        motion: Option<(f64, bool)>,
+        outages: usize,
+    ) -> Vec<String> {
+        let effect = &self.effects[camera];
+
+        let mut flags = Vec::new();
+        if self.mirror.load(Ordering::Relaxed) {
+            flags.push("MIRROR");
+        }
+        if self.frozen {
+            flags.push("FROZEN");
+        }
+        if extractor.trail_decay().is_some() {
+            flags.push("TRAILS");
+        }
+        if extractor.is_calibrating() {
+            flags.push("CALIBRATING");
+        } else if extractor.noise_floor().is_some() {
+            flags.push("NOISE GATE");
+        }
+
+        let unit = if self.delay_unit == DelayUnit::Milliseconds {
+            " MS"
+        } else {
+            ""
+        };
+        let delays = if let Some(background) = effect.background {
+            format!(
+                "BACKGROUND {} RATE {}",
+                background.name().to_ascii_uppercase(),
+                background.learning_rate()
+            )
+        } else if effect.luma {
+            format!("DELAY {}{}", effect.luma_delay, unit)
+        } else {
+            format!(
+                "DELAY R {} G {} B {}{}",
+                effect.delays.red, effect.delays.green, effect.delays.blue, unit
+            )
+        };
+
+        let settings = if effect.luma {
+            vec![
+                format!("LUMA MAP {}", effect.colormap.name().to_ascii_uppercase()),
+                delays,
+            ]
+        } else {
+            vec![
+                delays,
+                format!(
+                    "DIFF R {} G {} B {}",
+                    effect.modes.red.name(),
+                    effect.modes.green.name(),
+                    effect.modes.blue.name()
+                )
+                .to_ascii_uppercase(),
+            ]
+        };
+
+        let mut lines = vec![format!("FPS {:.1}", fps)];
+        lines.extend(settings);
+        if let Some(blobs) = blobs {
+            lines.push(format!("OBJECTS {}", blobs));
+        }
+        if let Some((score, active)) = motion {
+            let state = if active { " ACTIVE" } else { "" };
+            lines.push(format!("MOTION {:.4}{}", score, state));
+        }
+        if outages > 0 {
+            lines.push(format!("SIGNAL LOST {}X", outages));
+        }
+        lines.push(flags.join(" "));
+        lines
+    }
+}
@@ -0,0 +1,219 @@
+use std::io::Cursor;
+use std::{error, fmt};
+
+use jpeg_decoder::{Decoder as JpegDecoder, PixelFormat as JpegPixelFormat};
+use png::{ColorType, Decoder as PngDecoder, Transformations};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+use rayon::slice::ParallelSliceMut;
+
+use crate::frame::{Frame, PixelFormat};
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Jpeg(jpeg_decoder::Error),
+    Png(png::DecodingError),
+    /// The image uses a pixel layout that can not be converted to `0RGB`.
+    Unsupported(String),
+    /// The decoded image does not have the dimensions of the output buffer.
+    Size {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Jpeg(err) => write!(f, "invalid JPEG: {}", err),
+            DecodeError::Png(err) => write!(f, "invalid PNG: {}", err),
+            DecodeError::Unsupported(what) => write!(f, "unsupported pixel layout: {}", what),
+            DecodeError::Size { expected, actual } => write!(
+                f,
+                "expected a {}x{} image, got {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+impl From<jpeg_decoder::Error> for DecodeError {
+    fn from(err: jpeg_decoder::Error) -> Self {
+        DecodeError::Jpeg(err)
+    }
+}
+
+impl From<png::DecodingError> for DecodeError {
+    fn from(err: png::DecodingError) -> Self {
+        DecodeError::Png(err)
+    }
+}
+
+/// Decodes `frame` into `out` as `0RGB` pixels, one row of `width` pixels after another.
+pub fn decode(frame: &Frame, width: usize, height: usize, out: &mut [u32]) -> Result<(), DecodeError> {
+    match frame.format {
+        PixelFormat::Mjpeg => decode_jpeg(&frame.data, width, height, out),
+        PixelFormat::Png => decode_png(&frame.data, width, height, out),
+        PixelFormat::Rgb24 => decode_packed(frame, width, height, 3, out),
+        PixelFormat::Grey => decode_packed(frame, width, height, 1, out),
+        PixelFormat::Yuyv => decode_yuyv(frame, width, height, out),
+        PixelFormat::Nv12 => decode_nv12(frame, width, height, out),
+    }
+}
+
+/// Flips every row of `buf` horizontally, which turns a camera image into a mirror image.
+pub fn mirror(buf: &mut [u32], width: usize) {
+    buf.chunks_exact_mut(width).for_each(|row| row.reverse());
+}
+
+/// Reads the dimensions of an encoded image without decoding its pixels.
+pub fn probe(format: PixelFormat, data: &[u8]) -> Result<(usize, usize), DecodeError> {
+    match format {
+        PixelFormat::Mjpeg => {
+            let mut decoder = JpegDecoder::new(Cursor::new(data));
+            decoder.read_info()?;
+
+            let info = decoder.info().expect("Header has been read");
+            Ok((info.width as usize, info.height as usize))
+        }
+        PixelFormat::Rgb24 | PixelFormat::Yuyv | PixelFormat::Nv12 | PixelFormat::Grey => {
+            Err(DecodeError::Unsupported("raw frames carry no header".into()))
+        }
+        PixelFormat::Png => {
+            let info = PngDecoder::new(Cursor::new(data)).read_info()?.info().size();
+            Ok((info.0 as usize, info.1 as usize))
+        }
+    }
+}
+
+fn decode_jpeg(data: &[u8], width: usize, height: usize, out: &mut [u32]) -> Result<(), DecodeError> {
+    let mut decoder = JpegDecoder::new(Cursor::new(data));
+    let pixels = decoder.decode()?;
+
+    let info = decoder.info().expect("Image has been decoded");
+    check_size((width, height), (info.width as usize, info.height as usize))?;
+
+    match info.pixel_format {
+        JpegPixelFormat::RGB24 => store(&pixels, 3, out),
+        JpegPixelFormat::L8 => store(&pixels, 1, out),
+        other => return Err(DecodeError::Unsupported(format!("{:?} JPEG", other))),
+    }
+
+    Ok(())
+}
+
+fn decode_png(data: &[u8], width: usize, height: usize, out: &mut [u32]) -> Result<(), DecodeError> {
+    let mut decoder = PngDecoder::new(Cursor::new(data));
+    decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+
+    let mut reader = decoder.read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader.next_frame(&mut pixels)?;
+    check_size((width, height), (info.width as usize, info.height as usize))?;
+
+    let pixels = &pixels[..info.buffer_size()];
+    match info.color_type {
+        ColorType::Rgb => store(pixels, 3, out),
+        ColorType::Rgba => store(pixels, 4, out),
+        ColorType::Grayscale => store(pixels, 1, out),
+        ColorType::GrayscaleAlpha => store(pixels, 2, out),
+        other => return Err(DecodeError::Unsupported(format!("{:?} PNG", other))),
+    }
+
+    Ok(())
+}
+
+/// Converts a packed frame with `samples` bytes per pixel.
+fn decode_packed(
+    frame: &Frame,
+    width: usize,
+    height: usize,
+    samples: usize,
+    out: &mut [u32],
+) -> Result<(), DecodeError> {
+    let stride = raw_stride(frame, (width, height), width * samples, height)?;
+
+    out.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        store(&frame.data[y * stride..], samples, row);
+    });
+
+    Ok(())
+}
+
+fn decode_yuyv(frame: &Frame, width: usize, height: usize, out: &mut [u32]) -> Result<(), DecodeError> {
+    let stride = raw_stride(frame, (width, height), width.next_multiple_of(2) * 2, height)?;
+
+    out.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        let line = &frame.data[y * stride..];
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let pair = &line[(x & !1) * 2..];
+            *pixel = yuv_to_rgb(pair[(x & 1) * 2], pair[1], pair[3]);
+        }
+    });
+
+    Ok(())
+}
+
+fn decode_nv12(frame: &Frame, width: usize, height: usize, out: &mut [u32]) -> Result<(), DecodeError> {
+    let chroma_rows = height.div_ceil(2);
+    let stride = raw_stride(frame, (width, height), width.next_multiple_of(2), height + chroma_rows)?;
+    let (luma, chroma) = frame.data.split_at(stride * height);
+
+    out.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        let line = &luma[y * stride..];
+        let uv = &chroma[(y / 2) * stride..];
+        for (x, pixel) in row.iter_mut().enumerate() {
+            *pixel = yuv_to_rgb(line[x], uv[x & !1], uv[x | 1]);
+        }
+    });
+
+    Ok(())
+}
+
+/// Works out the length of a row in bytes from the frame size, given the bytes a row needs
+/// without padding and the number of rows stored in the frame.
+fn raw_stride(frame: &Frame, expected: (usize, usize), row_len: usize, rows: usize) -> Result<usize, DecodeError> {
+    check_size(expected, (frame.width, frame.height))?;
+
+    let stride = frame.data.len() / rows.max(1);
+    if stride < row_len {
+        return Err(DecodeError::Unsupported(format!("truncated {:?} frame", frame.format)));
+    }
+
+    Ok(stride)
+}
+
+/// Converts one limited range BT.601 sample, as cameras produce, to `0RGB`.
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> u32 {
+    let c = 298 * (y as i32 - 16);
+    let d = u as i32 - 128;
+    let e = v as i32 - 128;
+
+    let r = ((c + 409 * e + 128) >> 8).clamp(0, 255) as u32;
+    let g = ((c - 100 * d - 208 * e + 128) >> 8).clamp(0, 255) as u32;
+    let b = ((c + 516 * d + 128) >> 8).clamp(0, 255) as u32;
+
+    (r << 16) | (g << 8) | b
+}
+
+fn check_size(expected: (usize, usize), actual: (usize, usize)) -> Result<(), DecodeError> {
+    if expected != actual {
+        return Err(DecodeError::Size { expected, actual });
+    }
+
+    Ok(())
+}
+
+/// Packs interleaved 8-bit samples into `0RGB`. One or two samples per pixel are treated as grey
+/// (plus alpha), three or four as RGB (plus alpha); alpha is dropped.
+fn store(pixels: &[u8], samples: usize, out: &mut [u32]) {
+    for (pixel, chunk) in out.iter_mut().zip(pixels.chunks_exact(samples)) {
+        let (r, g, b) = match samples {
+            1 | 2 => (chunk[0], chunk[0], chunk[0]),
+            _ => (chunk[0], chunk[1], chunk[2]),
+        };
+
+        *pixel = ((r as u32) << 16) | ((g as u32) << 8) | b as u32;
+    }
+}
@@ -0,0 +1,194 @@
+/// Settings of a [`MotionDetector`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectorConfig {
+    /// Smallest motion magnitude, out of `255`, that counts as motion.
+    pub threshold: u8,
+    /// Passes of 3x3 erosion, which remove specks of noise from the mask.
+    pub erode: usize,
+    /// Passes of 3x3 dilation after eroding, which close gaps within moving objects.
+    pub dilate: usize,
+    /// Blobs of fewer pixels are dropped.
+    pub min_area: usize,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 32,
+            erode: 1,
+            dilate: 2,
+            min_area: 16,
+        }
+    }
+}
+
+/// A connected region of motion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Blob {
+    /// Left edge of the bounding box.
+    pub x: usize,
+    /// Top edge of the bounding box.
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    /// Number of pixels in the blob.
+    pub area: usize,
+    /// Mean position of the pixels in the blob.
+    pub centroid: (f64, f64),
+}
+
+/// Turns motion magnitudes into a binary mask and the blobs of motion within it.
+pub struct MotionDetector {
+    width: usize,
+    height: usize,
+    config: DetectorConfig,
+    mask: Vec<u8>,
+    scratch: Vec<u8>,
+    labels: Vec<u32>,
+    stack: Vec<usize>,
+    blobs: Vec<Blob>,
+}
+
+impl MotionDetector {
+    pub fn new(width: usize, height: usize, config: DetectorConfig) -> Self {
+        Self {
+            width,
+            height,
+            config,
+            mask: vec![0; width * height],
+            scratch: vec![0; width * height],
+            labels: vec![0; width * height],
+            stack: Vec::new(),
+            blobs: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> DetectorConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: DetectorConfig) {
+        self.config = config;
+    }
+
+    /// The mask of the last call to [`detect`](Self::detect), `1` where there is motion and `0`
+    /// elsewhere.
+    pub fn mask(&self) -> &[u8] {
+        &self.mask
+    }
+
+    /// The blobs found by the last call to [`detect`](Self::detect), in the order of their top
+    /// left pixel.
+    pub fn blobs(&self) -> &[Blob] {
+        &self.blobs
+    }
+
+    /// Thresholds `magnitude`, see [`MotionExtractor::magnitude`](crate::MotionExtractor::magnitude),
+    /// cleans the mask up and labels its 8-connected components.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `magnitude` is not exactly `width * height` values long.
+    pub fn detect(&mut self, magnitude: &[u8]) -> &[Blob] {
+        assert_eq!(
+            magnitude.len(),
+            self.width * self.height,
+            "Magnitude size does not match detector"
+        );
+
+        for (mask, &value) in self.mask.iter_mut().zip(magnitude) {
+            *mask = (value >= self.config.threshold) as u8;
+        }
+
+        for _ in 0..self.config.erode {
+            self.morph(u8::min);
+        }
+        for _ in 0..self.config.dilate {
+            self.morph(u8::max);
+        }
+
+        self.label();
+        &self.blobs
+    }
+
+    /// Applies `op` over the 3x3 neighbourhood of every pixel, one direction at a time. Pixels
+    /// outside the frame repeat the edge, so motion at the border is not eroded away.
+    fn morph(&mut self, op: fn(u8, u8) -> u8) {
+        let (width, height) = (self.width, self.height);
+
+        for y in 0..height {
+            let row = y * width;
+            for x in 0..width {
+                let left = self.mask[row + x.saturating_sub(1)];
+                let right = self.mask[row + (x + 1).min(width - 1)];
+                self.scratch[row + x] = op(op(left, self.mask[row + x]), right);
+            }
+        }
+
+        for y in 0..height {
+            let up = y.saturating_sub(1) * width;
+            let down = (y + 1).min(height - 1) * width;
+            for x in 0..width {
+                self.mask[y * width + x] = op(
+                    op(self.scratch[up + x], self.scratch[y * width + x]),
+                    self.scratch[down + x],
+                );
+            }
+        }
+    }
+
+    fn label(&mut self) {
+        let (width, height) = (self.width, self.height);
+        self.labels.fill(0);
+        self.blobs.clear();
+        let mut label = 0;
+
+        for start in 0..self.mask.len() {
+            if self.mask[start] == 0 || self.labels[start] != 0 {
+                continue;
+            }
+
+            label += 1;
+            let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+            let (mut area, mut sum_x, mut sum_y) = (0, 0, 0);
+
+            self.labels[start] = label;
+            self.stack.push(start);
+
+            while let Some(i) = self.stack.pop() {
+                let (x, y) = (i % width, i / width);
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+                area += 1;
+                sum_x += x;
+                sum_y += y;
+
+                for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                    for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                        let n = ny * width + nx;
+                        if self.mask[n] != 0 && self.labels[n] == 0 {
+                            self.labels[n] = label;
+                            self.stack.push(n);
+                        }
+                    }
+                }
+            }
+
+            // Small blobs keep their label so they are not visited again, but are not reported.
+            if area < self.config.min_area {
+                continue;
+            }
+
+            self.blobs.push(Blob {
+                x: min_x,
+                y: min_y,
+                width: max_x - min_x + 1,
+                height: max_y - min_y + 1,
+                area,
+                centroid: (sum_x as f64 / area as f64, sum_y as f64 / area as f64),
+            });
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::io::{self, Write};
+
+use jpeg_encoder::{ColorType as JpegColorType, Encoder as JpegEncoder};
+use png::{BitDepth, ColorType as PngColorType, Encoder as PngEncoder};
+
+/// Unpacks `0RGB` pixels into interleaved 8-bit RGB samples.
+pub fn to_rgb(pixels: &[u32]) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|&p| [(p >> 16) as u8, (p >> 8) as u8, p as u8])
+        .collect()
+}
+
+/// Encodes `0RGB` pixels as a baseline JPEG with the given `quality` (1-100).
+pub fn encode_jpeg(pixels: &[u32], width: usize, height: usize, quality: u8, out: impl Write) -> io::Result<()> {
+    let (width, height) = dimensions_u16(width, height)?;
+    JpegEncoder::new(out, quality)
+        .encode(&to_rgb(pixels), width, height, JpegColorType::Rgb)
+        .map_err(io::Error::other)
+}
+
+pub fn encode_png(pixels: &[u32], width: usize, height: usize, out: impl Write) -> io::Result<()> {
+    let mut encoder = PngEncoder::new(out, width as u32, height as u32);
+    encoder.set_color(PngColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&to_rgb(pixels)).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+/// Encodes `0RGB` pixels as a binary (`P6`) PPM image.
+pub fn encode_ppm(pixels: &[u32], width: usize, height: usize, mut out: impl Write) -> io::Result<()> {
+    write!(out, "P6\n{} {}\n255\n", width, height)?;
+    out.write_all(&to_rgb(pixels))
+}
+
+fn dimensions_u16(width: usize, height: usize) -> io::Result<(u16, u16)> {
+    match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "image is too large for JPEG",
+        )),
+    }
+}
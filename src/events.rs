@@ -0,0 +1,302 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::roi::RoiMask;
+
+/// How the motion magnitudes of a frame are summed up into a single score from `0` to `1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreKind {
+    /// Fraction of pixels that changed by at least a threshold.
+    #[default]
+    Changed,
+    /// Mean change of all pixels, relative to the largest possible change.
+    Energy,
+}
+
+impl FromStr for ScoreKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "changed" => Ok(ScoreKind::Changed),
+            "energy" => Ok(ScoreKind::Energy),
+            _ => Err(format!("unknown score {:?}, expected changed or energy", s)),
+        }
+    }
+}
+
+/// Scores the motion of a frame from its magnitudes, see
+/// [`MotionExtractor::magnitude`](crate::MotionExtractor::magnitude). `threshold` is the smallest
+/// magnitude [`ScoreKind::Changed`] counts. Only pixels inside `roi` are taken into account.
+pub fn motion_score(magnitude: &[u8], kind: ScoreKind, threshold: u8, roi: Option<&RoiMask>) -> f64 {
+    // Magnitudes outside the region are zero already, they only must not dilute the score.
+    let pixels = roi.map_or(magnitude.len(), RoiMask::area);
+    if pixels == 0 {
+        return 0.0;
+    }
+
+    let total = match kind {
+        ScoreKind::Changed => magnitude.iter().filter(|&&value| value >= threshold).count() as f64,
+        ScoreKind::Energy => magnitude.iter().map(|&value| value as u64).sum::<u64>() as f64 / 255.0,
+    };
+
+    total / pixels as f64
+}
+
+/// Settings of an [`EventDetector`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventConfig {
+    /// Score at or above which motion starts.
+    pub trigger: f64,
+    /// Score below which motion stops, normally lower than `trigger` so that an event is not
+    /// split up by a score that hovers around it.
+    pub release: f64,
+    /// How long the score has to stay at or above `trigger` for an event to start.
+    pub min_duration: Duration,
+    /// How long the score has to stay below `release` for an event to end.
+    pub release_after: Duration,
+}
+
+impl Default for EventConfig {
+    fn default() -> Self {
+        Self {
+            trigger: 0.01,
+            release: 0.005,
+            min_duration: Duration::from_millis(200),
+            release_after: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A period of motion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionEvent {
+    /// When the score first reached the trigger threshold.
+    pub start: SystemTime,
+    /// When the score first dropped below the release threshold for good.
+    pub end: SystemTime,
+    /// Highest score during the event.
+    pub peak: f64,
+}
+
+impl MotionEvent {
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start).unwrap_or_default()
+    }
+}
+
+/// Turns a stream of motion scores into [`MotionEvent`]s, with separate start and stop thresholds
+/// and minimum durations so that noise neither starts events nor cuts them short.
+pub struct EventDetector {
+    config: EventConfig,
+    /// When the score reached the trigger threshold, while waiting out the minimum duration.
+    triggered: Option<SystemTime>,
+    /// The event in progress.
+    active: Option<MotionEvent>,
+    /// When the score dropped below the release threshold during the event.
+    released: Option<SystemTime>,
+}
+
+impl EventDetector {
+    pub fn new(config: EventConfig) -> Self {
+        Self {
+            config,
+            triggered: None,
+            active: None,
+            released: None,
+        }
+    }
+
+    pub fn config(&self) -> EventConfig {
+        self.config
+    }
+
+    /// Whether an event is in progress.
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Feeds the score of a frame captured at `time`. Returns the event that ended with it, if any.
+    pub fn update(&mut self, score: f64, time: SystemTime) -> Option<MotionEvent> {
+        let Some(event) = &mut self.active else {
+            if score < self.config.trigger {
+                self.triggered = None;
+                return None;
+            }
+
+            let start = *self.triggered.get_or_insert(time);
+            if elapsed(start, time) >= self.config.min_duration {
+                self.triggered = None;
+                self.active = Some(MotionEvent {
+                    start,
+                    end: time,
+                    peak: score,
+                });
+            }
+
+            return None;
+        };
+
+        event.peak = event.peak.max(score);
+
+        if score >= self.config.release {
+            self.released = None;
+            return None;
+        }
+
+        let released = *self.released.get_or_insert(time);
+        if elapsed(released, time) < self.config.release_after {
+            return None;
+        }
+
+        self.finish(released)
+    }
+
+    /// Ends the event in progress at `time`, e.g. when the input ends.
+    pub fn finish(&mut self, time: SystemTime) -> Option<MotionEvent> {
+        self.triggered = None;
+        let end = self.released.take().unwrap_or(time);
+
+        self.active.take().map(|event| MotionEvent { end, ..event })
+    }
+}
+
+/// Tells when frames were captured, for events and clips. Frames of live sources are taken as
+/// captured when they arrive, frames of files and patterns at their time into the footage, so
+/// that events last as long in the footage however fast it is read.
+#[derive(Debug, Clone, Copy)]
+pub enum FrameClock {
+    Live,
+    Footage {
+        /// When the footage started.
+        start: SystemTime,
+        /// Timestamp of the first frame, which counts as captured at `start`.
+        first: Option<Duration>,
+        /// When the last frame was captured.
+        last: SystemTime,
+    },
+}
+
+impl FrameClock {
+    /// A clock for footage that starts at `start`.
+    pub fn footage(start: SystemTime) -> Self {
+        FrameClock::Footage {
+            start,
+            first: None,
+            last: start,
+        }
+    }
+
+    /// When the frame stamped with `timestamp` was captured, see
+    /// [`Frame::timestamp`](crate::Frame::timestamp).
+    pub fn time(&mut self, timestamp: Duration) -> SystemTime {
+        match self {
+            FrameClock::Live => SystemTime::now(),
+            FrameClock::Footage { start, first, last } => {
+                let first = *first.get_or_insert(timestamp);
+                *last = *start + timestamp.saturating_sub(first);
+                *last
+            }
+        }
+    }
+
+    /// The current time: now for live sources, when the last frame was captured for footage.
+    pub fn now(&self) -> SystemTime {
+        match self {
+            FrameClock::Live => SystemTime::now(),
+            FrameClock::Footage { last, .. } => *last,
+        }
+    }
+}
+
+/// Appends [`MotionEvent`]s to a file as JSON lines.
+pub struct EventLog<W: Write> {
+    writer: W,
+}
+
+impl EventLog<LineWriter<File>> {
+    /// Opens `path` for appending, creating it if it does not exist yet.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(LineWriter::new(file)))
+    }
+}
+
+impl<W: Write> EventLog<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes `event` as a line like
+    /// `{"start":"2024-05-01T12:00:00.000Z","end":"2024-05-01T12:00:02.500Z","duration":2.500,"peak":0.0420}`.
+    pub fn write(&mut self, event: &MotionEvent) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "{{\"start\":\"{}\",\"end\":\"{}\",\"duration\":{:.3},\"peak\":{:.4}}}",
+            format_utc(event.start),
+            format_utc(event.end),
+            event.duration().as_secs_f64(),
+            event.peak
+        )
+    }
+
+    /// Writes `event` like [`write`](Self::write) does, led by the `source` it was seen by, e.g.
+    /// `{"source":"/dev/video2","start":...}`.
+    pub fn write_from(&mut self, event: &MotionEvent, source: &str) -> io::Result<()> {
+        let source = source.replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(
+            self.writer,
+            "{{\"source\":\"{}\",\"start\":\"{}\",\"end\":\"{}\",\"duration\":{:.3},\"peak\":{:.4}}}",
+            source,
+            format_utc(event.start),
+            format_utc(event.end),
+            event.duration().as_secs_f64(),
+            event.peak
+        )
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn elapsed(since: SystemTime, now: SystemTime) -> Duration {
+    now.duration_since(since).unwrap_or_default()
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp with millisecond precision.
+pub(crate) fn format_utc(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, day_seconds) = ((seconds / 86_400) as i64, seconds % 86_400);
+
+    // Converts days since the epoch to a proleptic Gregorian date, counting years from March
+    // so that leap days fall at the end of a year.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        day_seconds / 3600,
+        day_seconds / 60 % 60,
+        day_seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
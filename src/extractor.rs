@@ -0,0 +1,760 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+
+use crate::background::{Background, BackgroundModel};
+use crate::colormap::Colormap;
+use crate::roi::RoiMask;
+use crate::smooth::{Smoothing, TemporalFilter};
+
+/// Time between frames the extractor assumes until told otherwise, that of 30 frames per second.
+pub const DEFAULT_FRAME_INTERVAL: Duration = Duration::from_nanos(33_333_333);
+
+/// How many frames, or milliseconds with [`DelayUnit::Milliseconds`], apart each colour channel of
+/// the output frame and the frame it is compared against are.
+///
+/// A delay of `0` compares a channel against itself, which always yields black. Negative delays
+/// compare against frames that arrive *after* the output frame; they are served by holding the
+/// output back by as many frames, see [`ChannelDelays::latency`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelDelays {
+    pub red: i32,
+    pub green: i32,
+    pub blue: i32,
+}
+
+impl ChannelDelays {
+    pub fn new(red: i32, green: i32, blue: i32) -> Self {
+        Self { red, green, blue }
+    }
+
+    /// Number of frames the output lags behind the newest frame to serve negative delays.
+    pub fn latency(&self) -> usize {
+        (-self.red.min(self.green).min(self.blue)).max(0) as usize
+    }
+
+    /// Number of frames that have to be kept around to serve every delay.
+    pub fn history_len(&self) -> usize {
+        self.red.max(self.green).max(self.blue).max(0) as usize + self.latency() + 1
+    }
+}
+
+impl Default for ChannelDelays {
+    fn default() -> Self {
+        Self::new(1, 5, 9)
+    }
+}
+
+/// What the numbers of [`ChannelDelays`] count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DelayUnit {
+    /// Frames, however far apart in time they were captured.
+    #[default]
+    Frames,
+    /// Milliseconds between capture timestamps, served by the frame captured closest to the
+    /// delay. The effect stays the same when the camera drops frames or changes its frame rate.
+    Milliseconds,
+}
+
+impl DelayUnit {
+    pub fn name(self) -> &'static str {
+        match self {
+            DelayUnit::Frames => "frames",
+            DelayUnit::Milliseconds => "ms",
+        }
+    }
+}
+
+impl FromStr for DelayUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "frames" | "frame" => Ok(DelayUnit::Frames),
+            "ms" | "milliseconds" => Ok(DelayUnit::Milliseconds),
+            _ => Err(format!("unknown delay unit {:?}, expected frames or ms", s)),
+        }
+    }
+}
+
+/// How a channel of the output frame is compared against the same channel of an older frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffMode {
+    /// Only channels that got brighter show up, anything that got darker is black.
+    #[default]
+    Saturating,
+    /// Channels that got brighter or darker show up alike.
+    Absolute,
+    /// Half the signed difference around mid-grey, like overlaying an inverted copy of the older
+    /// frame at 50% opacity. Still areas are grey, brightening is lighter and darkening darker.
+    Signed,
+}
+
+impl DiffMode {
+    /// The next mode in the order they are listed in, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            DiffMode::Saturating => DiffMode::Absolute,
+            DiffMode::Absolute => DiffMode::Signed,
+            DiffMode::Signed => DiffMode::Saturating,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DiffMode::Saturating => "saturating",
+            DiffMode::Absolute => "absolute",
+            DiffMode::Signed => "signed",
+        }
+    }
+
+    /// Diffs two 8-bit channel values.
+    #[inline]
+    pub fn apply(self, current: u32, older: u32) -> u32 {
+        match self {
+            DiffMode::Saturating => current.saturating_sub(older),
+            DiffMode::Absolute => current.abs_diff(older),
+            DiffMode::Signed => (0x100 + current - older) >> 1,
+        }
+    }
+}
+
+impl FromStr for DiffMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "saturating" | "sat" => Ok(DiffMode::Saturating),
+            "absolute" | "abs" => Ok(DiffMode::Absolute),
+            "signed" => Ok(DiffMode::Signed),
+            _ => Err(format!(
+                "unknown diff mode {:?}, expected saturating, absolute or signed",
+                s
+            )),
+        }
+    }
+}
+
+/// The [`DiffMode`] of each colour channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChannelModes {
+    pub red: DiffMode,
+    pub green: DiffMode,
+    pub blue: DiffMode,
+}
+
+impl ChannelModes {
+    pub fn new(red: DiffMode, green: DiffMode, blue: DiffMode) -> Self {
+        Self { red, green, blue }
+    }
+
+    /// Uses `mode` for every channel.
+    pub fn all(mode: DiffMode) -> Self {
+        Self::new(mode, mode, mode)
+    }
+}
+
+/// What the extractor diffs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MotionMode {
+    /// Each colour channel against its own delay, which splits motion into colours by time.
+    #[default]
+    Rgb,
+    /// The absolute difference of the luma of a frame and the frame `delay` frames before it,
+    /// shown through a colour map.
+    Luma { delay: i32, colormap: Colormap },
+}
+
+/// Keeps a short history of `0RGB` frames and diffs each colour channel of a frame against the
+/// same channel of another frame from that history, or the luma of two frames in
+/// [`MotionMode::Luma`]. With a [`Background`] model, frames are diffed against the learned
+/// background instead and the delays go unused.
+pub struct MotionExtractor {
+    width: usize,
+    height: usize,
+    delays: ChannelDelays,
+    delay_unit: DelayUnit,
+    /// Nominal time between frames, which frames pushed without a timestamp are stamped with.
+    frame_interval: Duration,
+    modes: ChannelModes,
+    mode: MotionMode,
+    palette: [u32; 256],
+    roi: Option<RoiMask>,
+    /// Per-pixel changes below which a pixel counts as unchanged.
+    noise_floor: Option<Vec<u8>>,
+    calibration: Option<Calibration>,
+    filter: TemporalFilter,
+    background: Option<BackgroundModel>,
+    /// Factor the motion trails fade by every frame, if trails are on.
+    trail_decay: Option<f32>,
+    back_buffer: VecDeque<Vec<u32>>,
+    /// Number of frames at the front of the history that only fill it up until enough frames
+    /// were pushed, black and stamped zero.
+    blank: usize,
+    /// Capture time of every frame in the history.
+    timestamps: VecDeque<Duration>,
+    diff_buf: Vec<u32>,
+    /// Diff frames summed up over time, per channel and relative to what shows no change.
+    accumulation: Vec<[f32; 3]>,
+}
+
+impl MotionExtractor {
+    pub fn new(width: usize, height: usize, delays: ChannelDelays) -> Self {
+        let back_buffer = VecDeque::from(vec![vec![0; width * height]; delays.history_len()]);
+        let timestamps = VecDeque::from(vec![Duration::ZERO; delays.history_len()]);
+
+        Self {
+            width,
+            height,
+            delays,
+            delay_unit: DelayUnit::Frames,
+            frame_interval: DEFAULT_FRAME_INTERVAL,
+            modes: ChannelModes::default(),
+            mode: MotionMode::default(),
+            palette: Colormap::default().palette(),
+            roi: None,
+            noise_floor: None,
+            calibration: None,
+            filter: TemporalFilter::new(Smoothing::Off),
+            background: None,
+            trail_decay: None,
+            back_buffer,
+            blank: delays.history_len(),
+            timestamps,
+            diff_buf: vec![0; width * height],
+            accumulation: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn delays(&self) -> ChannelDelays {
+        self.delays
+    }
+
+    pub fn modes(&self) -> ChannelModes {
+        self.modes
+    }
+
+    pub fn mode(&self) -> MotionMode {
+        self.mode
+    }
+
+    /// Changes how each channel is diffed, effective from the next frame.
+    pub fn set_modes(&mut self, modes: ChannelModes) {
+        if self.modes != modes {
+            self.reset_trails();
+        }
+
+        self.modes = modes;
+    }
+
+    /// Changes the delays, growing or shrinking the history to match. Frames added to the
+    /// history are copies of the oldest frame, so the change does not flash the whole image.
+    pub fn set_delays(&mut self, delays: ChannelDelays) {
+        self.delays = delays;
+        self.resize_history();
+    }
+
+    /// Number of frames in the history.
+    pub fn history_len(&self) -> usize {
+        self.back_buffer.len()
+    }
+
+    pub fn delay_unit(&self) -> DelayUnit {
+        self.delay_unit
+    }
+
+    /// Changes what the delays count, effective from the next frame.
+    pub fn set_delay_unit(&mut self, unit: DelayUnit) {
+        self.delay_unit = unit;
+        self.resize_history();
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        self.frame_interval
+    }
+
+    /// Sets the nominal time between frames, which sizes the history for delays in milliseconds
+    /// and stamps frames pushed with [`push_frame`](Self::push_frame).
+    pub fn set_frame_interval(&mut self, interval: Duration) {
+        self.frame_interval = interval;
+        self.resize_history();
+    }
+
+    pub fn roi(&self) -> Option<&RoiMask> {
+        self.roi.as_ref()
+    }
+
+    /// Restricts motion to the pixels inside `roi`, or lifts the restriction if it is `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mask is not the size of the frames.
+    pub fn set_roi(&mut self, roi: Option<RoiMask>) {
+        if let Some(roi) = &roi {
+            assert_eq!(
+                (roi.width(), roi.height()),
+                (self.width, self.height),
+                "Mask size does not match extractor"
+            );
+        }
+
+        self.roi = roi;
+    }
+
+    pub fn noise_floor(&self) -> Option<&[u8]> {
+        self.noise_floor.as_deref()
+    }
+
+    /// Treats changes below `threshold` as noise, in every channel and every pixel, or stops
+    /// suppressing noise if it is `None`.
+    pub fn set_noise_threshold(&mut self, threshold: Option<u8>) {
+        self.noise_floor = threshold.map(|threshold| vec![threshold; self.width * self.height]);
+    }
+
+    /// Learns the noise floor of every pixel from the next `frames` frames, which should show
+    /// nothing but a still scene. Afterwards changes up to the largest one seen during calibration
+    /// are treated as noise. The current noise floor stays in use until calibration completes.
+    /// Right after [`new`](Self::new), frames only count once enough of them were pushed to fill
+    /// the history.
+    pub fn calibrate_noise(&mut self, frames: usize) {
+        self.calibration = (frames > 0).then(|| Calibration {
+            remaining: frames,
+            floor: vec![0; self.width * self.height],
+        });
+    }
+
+    pub fn is_calibrating(&self) -> bool {
+        self.calibration.is_some()
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        self.filter.smoothing()
+    }
+
+    /// Averages the output over consecutive frames, starting over from the next frame.
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.filter = TemporalFilter::new(smoothing);
+    }
+
+    pub fn background(&self) -> Option<Background> {
+        self.background.as_ref().map(BackgroundModel::background)
+    }
+
+    /// Diffs frames against a background model, which starts learning from the next frame, or
+    /// against the delayed frames again if `background` is `None`. Keeps the current model if it
+    /// is unchanged.
+    pub fn set_background(&mut self, background: Option<Background>) {
+        if self.background() != background {
+            self.background = background.map(BackgroundModel::new);
+            self.resize_history();
+        }
+    }
+
+    pub fn trail_decay(&self) -> Option<f32> {
+        self.trail_decay
+    }
+
+    /// Sums the output up over time like a long exposure, fading it by `decay`, between `0` and
+    /// `1`, every frame. Turns the trails off if it is `None`.
+    pub fn set_trails(&mut self, decay: Option<f32>) {
+        self.trail_decay = decay;
+        self.reset_trails();
+    }
+
+    /// Clears the motion trails built up so far.
+    pub fn reset_trails(&mut self) {
+        self.accumulation.clear();
+    }
+
+    /// Switches between RGB and luma motion, resizing the history like
+    /// [`set_delays`](Self::set_delays) does.
+    pub fn set_mode(&mut self, mode: MotionMode) {
+        if let MotionMode::Luma { colormap, .. } = mode {
+            self.palette = colormap.palette();
+        }
+
+        // Trails of RGB and luma motion do not mix.
+        if matches!(mode, MotionMode::Luma { .. }) != matches!(self.mode, MotionMode::Luma { .. }) {
+            self.reset_trails();
+        }
+
+        self.mode = mode;
+        self.resize_history();
+    }
+
+    /// The delays the history has to serve in the current mode.
+    fn active_delays(&self) -> ChannelDelays {
+        if self.background.is_some() {
+            return ChannelDelays::new(0, 0, 0);
+        }
+
+        match self.mode {
+            MotionMode::Rgb => self.delays,
+            MotionMode::Luma { delay, .. } => ChannelDelays::new(delay, delay, delay),
+        }
+    }
+
+    /// Writes how much each pixel of the last output frame changed into `out`, from `0` to `255`
+    /// regardless of the diff modes and colour map: the largest absolute channel difference, or
+    /// the absolute luma difference in luma mode.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` is not exactly `width * height` values long.
+    pub fn magnitude(&self, out: &mut [u8]) {
+        assert_eq!(
+            out.len(),
+            self.width * self.height,
+            "Buffer size does not match extractor"
+        );
+
+        let (base, frames) = compared_frames(
+            &self.back_buffer,
+            self.timed_history(),
+            self.active_delays(),
+            self.background.as_ref(),
+        );
+        let luma_mode = matches!(self.mode, MotionMode::Luma { .. });
+        let floor = self.noise_floor.as_deref();
+
+        out.par_iter_mut().enumerate().for_each(|(i, value)| {
+            let change = change(base, frames, i, luma_mode);
+            *value = if floor.is_some_and(|floor| change < floor[i] as u32) {
+                0
+            } else {
+                change as u8
+            };
+        });
+
+        if let Some(roi) = &self.roi {
+            clear_outside(out, roi, 0);
+        }
+    }
+
+    /// The timestamps of the history, if frames are picked by them.
+    fn timed_history(&self) -> Option<&VecDeque<Duration>> {
+        (self.delay_unit == DelayUnit::Milliseconds).then_some(&self.timestamps)
+    }
+
+    /// Most frames the history holds: exactly as many as the delays need when they count frames,
+    /// and room for frames arriving at up to twice the nominal rate when they are milliseconds.
+    fn history_capacity(&self) -> usize {
+        let len = self.active_delays().history_len();
+        match self.delay_unit {
+            DelayUnit::Frames => len,
+            DelayUnit::Milliseconds => {
+                let frames = (len - 1) as f64 / 1000.0 / self.frame_interval.as_secs_f64().max(1e-6);
+                2 * frames.ceil() as usize + 2
+            }
+        }
+    }
+
+    /// Shrinks the history to its capacity. Frames counted delays need a full history, so frames
+    /// added to it are copies of the oldest frame, which keeps the change from flashing the
+    /// whole image.
+    fn resize_history(&mut self) {
+        let len = self.history_capacity();
+
+        while self.back_buffer.len() > len {
+            self.pop_oldest();
+        }
+
+        while self.delay_unit == DelayUnit::Frames && self.back_buffer.len() < len {
+            let oldest = self.back_buffer.front().expect("History is never empty").clone();
+            self.back_buffer.push_front(oldest);
+            self.timestamps.push_front(self.timestamps[0]);
+            if self.blank > 0 {
+                self.blank += 1;
+            }
+        }
+    }
+
+    /// Removes the oldest frame from the history and returns it.
+    fn pop_oldest(&mut self) -> Vec<u32> {
+        self.blank = self.blank.saturating_sub(1);
+        self.timestamps.pop_front();
+        self.back_buffer.pop_front().expect("History is never empty")
+    }
+
+    /// Drops the frames from before the delays in milliseconds reach back, keeping the newest
+    /// frame captured at or before that.
+    fn trim_history(&mut self) {
+        let span = Duration::from_millis(self.active_delays().history_len() as u64 - 1);
+        let reach = self
+            .timestamps
+            .back()
+            .expect("History is never empty")
+            .saturating_sub(span);
+
+        while self.timestamps.len() > 1 && self.timestamps[1] <= reach {
+            self.pop_oldest();
+        }
+    }
+
+    /// Appends `frame` to the history and returns the motion extracted from the frame
+    /// [`latency`](ChannelDelays::latency) before it. The frame is stamped one
+    /// [`frame_interval`](Self::frame_interval) after the previous one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame` is not exactly `width * height` pixels long.
+    pub fn push_frame(&mut self, frame: &[u32]) -> &[u32] {
+        let timestamp = *self.timestamps.back().expect("History is never empty") + self.frame_interval;
+        self.push_frame_at(frame, timestamp)
+    }
+
+    /// Like [`push_frame`](Self::push_frame), for a frame captured at `timestamp`. Timestamps only
+    /// matter to delays in milliseconds; one earlier than the previous frame's counts as equal to
+    /// it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame` is not exactly `width * height` pixels long.
+    pub fn push_frame_at(&mut self, frame: &[u32], timestamp: Duration) -> &[u32] {
+        assert_eq!(
+            frame.len(),
+            self.width * self.height,
+            "Frame size does not match extractor"
+        );
+
+        let timestamp = timestamp.max(*self.timestamps.back().expect("History is never empty"));
+
+        // Once the history is full, the oldest frame's allocation can be reused.
+        let mut slot = if self.back_buffer.len() >= self.history_capacity() {
+            self.pop_oldest()
+        } else {
+            vec![0; self.width * self.height]
+        };
+        slot.copy_from_slice(frame);
+        self.back_buffer.push_back(slot);
+        self.timestamps.push_back(timestamp);
+
+        if self.delay_unit == DelayUnit::Milliseconds {
+            self.trim_history();
+        }
+
+        if let Some(model) = &mut self.background {
+            model.update(frame);
+        }
+
+        let (base, [frame_r, frame_g, frame_b]) = compared_frames(
+            &self.back_buffer,
+            self.timed_history(),
+            self.active_delays(),
+            self.background.as_ref(),
+        );
+
+        let floor = self.noise_floor.as_deref();
+        // Changes below the noise floor are diffed as if the channel had not changed at all.
+        let gate = |i: usize, current: u32, older: u32| match floor {
+            Some(floor) if current.abs_diff(older) < floor[i] as u32 => current,
+            _ => older,
+        };
+
+        // Luma motion is kept as grey until the colour map is applied, so that trails sum up the
+        // change rather than its colour.
+        let luma_mode = matches!(self.mode, MotionMode::Luma { .. });
+        if luma_mode {
+            self.diff_buf.par_iter_mut().enumerate().for_each(|(i, pixel)| {
+                let current = luma(base[i]);
+                *pixel = current.abs_diff(gate(i, current, luma(frame_r[i]))) * 0x010101;
+            });
+        } else {
+            let modes = self.modes;
+
+            self.diff_buf.par_iter_mut().enumerate().for_each(|(i, pixel)| {
+                let [r, g, b] = channels(base[i]);
+                let dr = modes.red.apply(r, gate(i, r, (frame_r[i] >> 16) & 0xFF));
+                let dg = modes.green.apply(g, gate(i, g, (frame_g[i] >> 8) & 0xFF));
+                let db = modes.blue.apply(b, gate(i, b, frame_b[i] & 0xFF));
+
+                *pixel = (dr << 16) | (dg << 8) | db;
+            });
+        }
+
+        // Frames are only compared against pushed frames once the blank ones are gone, noise
+        // measured against those would be the whole picture.
+        if let Some(calibration) = &mut self.calibration
+            && self.blank == 0
+        {
+            calibration.floor.par_iter_mut().enumerate().for_each(|(i, floor)| {
+                *floor = (*floor).max(change(base, [frame_r, frame_g, frame_b], i, luma_mode) as u8);
+            });
+
+            calibration.remaining -= 1;
+            if calibration.remaining == 0 {
+                let floor = self.calibration.take().expect("Calibration is in progress").floor;
+                self.noise_floor = Some(floor.into_iter().map(|floor| floor.saturating_add(1)).collect());
+            }
+        }
+
+        if let Some(decay) = self.trail_decay {
+            self.accumulate(decay);
+        }
+
+        if luma_mode {
+            let palette = &self.palette;
+            self.diff_buf
+                .par_iter_mut()
+                .for_each(|pixel| *pixel = palette[(*pixel & 0xFF) as usize]);
+        }
+
+        self.filter.apply(&mut self.diff_buf);
+
+        if let Some(roi) = &self.roi {
+            // Outside the region nothing moved, so it looks like still pixels inside it.
+            let still = match self.mode {
+                MotionMode::Rgb => {
+                    let [r, g, b] = self.neutral();
+                    (r << 16) | (g << 8) | b
+                }
+                MotionMode::Luma { .. } => self.palette[0],
+            };
+            clear_outside(&mut self.diff_buf, roi, still);
+        }
+
+        &self.diff_buf
+    }
+
+    /// Adds the diff frame to the trails, which fade by `decay`, and replaces it with the trails.
+    fn accumulate(&mut self, decay: f32) {
+        if self.accumulation.len() != self.diff_buf.len() {
+            self.accumulation = vec![[0.0; 3]; self.diff_buf.len()];
+        }
+
+        let neutral = self.neutral().map(|value| value as f32);
+
+        self.diff_buf
+            .par_iter_mut()
+            .zip(&mut self.accumulation)
+            .for_each(|(pixel, accumulated)| {
+                let mut sum = [0; 3];
+                for (c, channel) in channels(*pixel).into_iter().enumerate() {
+                    let value = accumulated[c] * decay + channel as f32 - neutral[c];
+                    accumulated[c] = value.clamp(-neutral[c], 255.0 - neutral[c]);
+                    sum[c] = (accumulated[c] + neutral[c]).round() as u32;
+                }
+                *pixel = (sum[0] << 16) | (sum[1] << 8) | sum[2];
+            });
+    }
+
+    /// What each channel of the diff frame holds when nothing changed, before the colour map:
+    /// mid-grey in signed mode, black otherwise.
+    fn neutral(&self) -> [u32; 3] {
+        let neutral = |mode: DiffMode| if mode == DiffMode::Signed { 0x80 } else { 0 };
+        match self.mode {
+            MotionMode::Rgb => [self.modes.red, self.modes.green, self.modes.blue].map(neutral),
+            MotionMode::Luma { .. } => [0; 3],
+        }
+    }
+}
+
+/// The frame the output is made of and the frames its red, green and blue channels are compared
+/// against. In luma mode or with a background model all three are the same frame. With the
+/// `timestamps` of the history, delays are milliseconds and served by the closest frames.
+fn compared_frames<'a>(
+    back_buffer: &'a VecDeque<Vec<u32>>,
+    timestamps: Option<&VecDeque<Duration>>,
+    delays: ChannelDelays,
+    background: Option<&'a BackgroundModel>,
+) -> (&'a [u32], [&'a [u32]; 3]) {
+    if let Some(model) = background {
+        let reference = model.reference();
+        return (back_buffer.back().expect("History is never empty"), [reference; 3]);
+    }
+
+    if let Some(timestamps) = timestamps {
+        let newest = *timestamps.back().expect("History is never empty");
+        let current = closest(timestamps, newest.saturating_sub(millis(delays.latency() as i32)));
+        let older = |delay: i32| {
+            let time = if delay >= 0 {
+                timestamps[current].saturating_sub(millis(delay))
+            } else {
+                timestamps[current] + millis(-delay)
+            };
+            back_buffer[closest(timestamps, time)].as_slice()
+        };
+
+        return (
+            &back_buffer[current],
+            [older(delays.red), older(delays.green), older(delays.blue)],
+        );
+    }
+
+    let current = back_buffer.len() - 1 - delays.latency();
+    let older = |delay: i32| back_buffer[current.wrapping_add_signed(-delay as isize)].as_slice();
+
+    (
+        &back_buffer[current],
+        [older(delays.red), older(delays.green), older(delays.blue)],
+    )
+}
+
+/// Index of the timestamp closest to `time` in the sorted `timestamps`, the older one on a tie.
+fn closest(timestamps: &VecDeque<Duration>, time: Duration) -> usize {
+    let after = timestamps.partition_point(|timestamp| *timestamp < time);
+    if after == 0 {
+        return 0;
+    }
+    if after == timestamps.len() || time - timestamps[after - 1] <= timestamps[after] - time {
+        after - 1
+    } else {
+        after
+    }
+}
+
+fn millis(delay: i32) -> Duration {
+    Duration::from_millis(delay as u64)
+}
+
+/// Noise floor measurement in progress.
+struct Calibration {
+    remaining: usize,
+    /// Largest change of every pixel so far.
+    floor: Vec<u8>,
+}
+
+/// How much pixel `i` of `base` changed compared to `frames`: the largest absolute channel
+/// difference, or the absolute luma difference in luma mode.
+#[inline]
+fn change(base: &[u32], [frame_r, frame_g, frame_b]: [&[u32]; 3], i: usize, luma_mode: bool) -> u32 {
+    let p = base[i];
+    if luma_mode {
+        return luma(p).abs_diff(luma(frame_r[i]));
+    }
+
+    let [r, g, b] = channels(p);
+    let dr = r.abs_diff((frame_r[i] >> 16) & 0xFF);
+    let dg = g.abs_diff((frame_g[i] >> 8) & 0xFF);
+    let db = b.abs_diff(frame_b[i] & 0xFF);
+    dr.max(dg).max(db)
+}
+
+#[inline]
+fn channels(pixel: u32) -> [u32; 3] {
+    [(pixel >> 16) & 0xFF, (pixel >> 8) & 0xFF, pixel & 0xFF]
+}
+
+/// Sets every value of `buf` outside `roi` to `fill`.
+fn clear_outside<T: Copy + Send + Sync>(buf: &mut [T], roi: &RoiMask, fill: T) {
+    buf.par_iter_mut()
+        .zip(roi.as_slice())
+        .filter(|(_, included)| **included == 0)
+        .for_each(|(value, _)| *value = fill);
+}
+
+/// BT.601 luma of a `0RGB` pixel.
+#[inline]
+fn luma(pixel: u32) -> u32 {
+    (77 * ((pixel >> 16) & 0xFF) + 150 * ((pixel >> 8) & 0xFF) + 29 * (pixel & 0xFF)) >> 8
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+/// Encoding of the bytes carried by a [`Frame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// A single JPEG image, as produced by MJPEG cameras and AVI files.
+    Mjpeg,
+    /// A single PNG image.
+    Png,
+    /// Packed 8-bit RGB, three bytes per pixel.
+    Rgb24,
+    /// Packed 4:2:2 YUV, two pixels in four bytes ordered Y0, U, Y1, V.
+    Yuyv,
+    /// Planar 4:2:0 YUV: a full resolution Y plane followed by interleaved U and V samples for
+    /// every 2x2 block.
+    Nv12,
+    /// 8-bit luma only.
+    Grey,
+}
+
+impl PixelFormat {
+    /// Maps a V4L2 FourCC code to the format it delivers, if it is one that can be decoded.
+    pub fn from_fourcc(fourcc: &[u8; 4]) -> Option<Self> {
+        match fourcc {
+            b"MJPG" | b"JPEG" => Some(PixelFormat::Mjpeg),
+            b"PNG " => Some(PixelFormat::Png),
+            b"RGB3" => Some(PixelFormat::Rgb24),
+            b"YUYV" => Some(PixelFormat::Yuyv),
+            b"NV12" => Some(PixelFormat::Nv12),
+            b"GREY" => Some(PixelFormat::Grey),
+            _ => None,
+        }
+    }
+
+    pub fn fourcc(&self) -> &'static [u8; 4] {
+        match self {
+            PixelFormat::Mjpeg => b"MJPG",
+            PixelFormat::Png => b"PNG ",
+            PixelFormat::Rgb24 => b"RGB3",
+            PixelFormat::Yuyv => b"YUYV",
+            PixelFormat::Nv12 => b"NV12",
+            PixelFormat::Grey => b"GREY",
+        }
+    }
+}
+
+/// An undecoded frame as it comes out of a capture device or file.
+///
+/// Uncompressed formats may pad their rows, the row stride is derived from the length of `data`.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub format: PixelFormat,
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+    /// When the frame was captured: the buffer timestamp of cameras, on the monotonic clock, or
+    /// the playback position of files and test patterns.
+    pub timestamp: Duration,
+    /// Number of the frame since capture started. Gaps mean frames were dropped on the way.
+    pub sequence: u64,
+}
@@ -0,0 +1,215 @@
+use std::time::Instant;
+
+use motion_extraction::Blob;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+/// Measures the rate at which frames are shown, smoothed over the last few frames.
+pub struct FpsCounter {
+    last: Option<Instant>,
+    fps: f64,
+}
+
+impl FpsCounter {
+    pub fn new() -> Self {
+        Self { last: None, fps: 0.0 }
+    }
+
+    /// Records that a frame has been shown and returns the updated frame rate.
+    pub fn tick(&mut self) -> f64 {
+        let now = Instant::now();
+        if let Some(last) = self.last.replace(now) {
+            let fps = 1.0 / now.duration_since(last).as_secs_f64().max(1e-6);
+            self.fps = if self.fps == 0.0 {
+                fps
+            } else {
+                self.fps * 0.9 + fps * 0.1
+            };
+        }
+
+        self.fps
+    }
+}
+
+/// Draws `lines` of text into the top left corner of `buf` on a darkened background.
+pub fn draw(buf: &mut [u32], width: usize, height: usize, lines: &[String]) {
+    let scale = (height / 360).max(1);
+    let (advance, line_height, margin) = ((GLYPH_WIDTH + 1) * scale, (GLYPH_HEIGHT + 2) * scale, 4 * scale);
+
+    let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+    let box_width = (2 * margin + columns * advance).min(width);
+    let box_height = (2 * margin + lines.len() * line_height).min(height);
+
+    for row in buf.chunks_exact_mut(width).take(box_height) {
+        for pixel in &mut row[..box_width] {
+            *pixel = (*pixel >> 2) & 0x3F3F3F;
+        }
+    }
+
+    for (n, line) in lines.iter().enumerate() {
+        for (i, c) in line.chars().enumerate() {
+            let (x0, y0) = (margin + i * advance, margin + n * line_height);
+
+            for (dy, bits) in glyph(c).iter().enumerate() {
+                for dx in 0..GLYPH_WIDTH {
+                    if bits & (0x10 >> dx) == 0 {
+                        continue;
+                    }
+
+                    for y in y0 + dy * scale..y0 + (dy + 1) * scale {
+                        for x in x0 + dx * scale..x0 + (dx + 1) * scale {
+                            if x < width && y < height {
+                                buf[y * width + x] = 0xFFFFFF;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Outlines the bounding box of every blob in `colour`, with lines as thick as the text.
+pub fn draw_blobs(buf: &mut [u32], width: usize, height: usize, blobs: &[Blob], colour: u32) {
+    let thickness = (height / 360).max(1);
+
+    for blob in blobs {
+        draw_rect(
+            buf,
+            width,
+            height,
+            (blob.x, blob.y, blob.width, blob.height),
+            thickness,
+            colour,
+        );
+    }
+}
+
+/// Outlines the `(x, y, width, height)` rectangle `rect` in `colour`, with lines `thickness`
+/// pixels wide.
+pub fn draw_rect(
+    buf: &mut [u32],
+    width: usize,
+    height: usize,
+    (x0, y0, rect_width, rect_height): (usize, usize, usize, usize),
+    thickness: usize,
+    colour: u32,
+) {
+    let (right, bottom) = (x0 + rect_width, y0 + rect_height);
+
+    for y in y0..bottom.min(height) {
+        for x in x0..right.min(width) {
+            let edge = x < x0 + thickness || y < y0 + thickness || x + thickness >= right || y + thickness >= bottom;
+            if edge {
+                buf[y * width + x] = colour;
+            }
+        }
+    }
+}
+
+/// Copies `frame`, of `frame_width` by `frame_height` pixels, into the `(x, y, width, height)`
+/// rectangle `rect` of `buf`, scaling it to fit by repeating or skipping pixels.
+pub fn blit(
+    buf: &mut [u32],
+    buf_width: usize,
+    (x0, y0, width, height): (usize, usize, usize, usize),
+    frame: &[u32],
+    (frame_width, frame_height): (usize, usize),
+) {
+    for y in 0..height {
+        let source = &frame[y * frame_height / height * frame_width..][..frame_width];
+        let row = &mut buf[(y0 + y) * buf_width + x0..][..width];
+
+        if width == frame_width {
+            row.copy_from_slice(source);
+        } else {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = source[x * frame_width / width];
+            }
+        }
+    }
+}
+
+/// Draws straight lines between consecutive `points`, marking each point with a small square.
+pub fn draw_path(buf: &mut [u32], width: usize, height: usize, points: &[(f64, f64)], colour: u32) {
+    let mut plot = |x: i64, y: i64| {
+        if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+            buf[y as usize * width + x as usize] = colour;
+        }
+    };
+
+    for pair in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+        let steps = (x1 - x0).abs().max((y1 - y0).abs()).ceil().max(1.0);
+
+        for step in 0..=steps as i64 {
+            let t = step as f64 / steps;
+            plot((x0 + (x1 - x0) * t) as i64, (y0 + (y1 - y0) * t) as i64);
+        }
+    }
+
+    let size = (height / 360).max(1) as i64 * 2;
+    for &(x, y) in points {
+        for dy in -size..=size {
+            for dx in -size..=size {
+                plot(x as i64 + dx, y as i64 + dy);
+            }
+        }
+    }
+}
+
+/// 5x7 bitmap of `c`, one byte per row with the leftmost pixel in bit 4. Letters are drawn in
+/// upper case, characters without a glyph as a question mark.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
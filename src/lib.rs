@@ -0,0 +1,38 @@
+mod avi;
+mod background;
+mod clips;
+mod colormap;
+mod decode;
+mod detect;
+mod encode;
+mod events;
+mod extractor;
+mod frame;
+mod output;
+mod prefilter;
+mod roi;
+mod smooth;
+mod source;
+mod stats;
+
+pub use avi::{AviReader, AviWriter};
+pub use background::{Background, BackgroundModel, DEFAULT_LEARNING_RATE};
+pub use clips::ClipRecorder;
+pub use colormap::Colormap;
+pub use decode::{DecodeError, decode, mirror, probe};
+pub use detect::{Blob, DetectorConfig, MotionDetector};
+pub use encode::{encode_jpeg, encode_png, encode_ppm, to_rgb};
+pub use events::{EventConfig, EventDetector, EventLog, FrameClock, MotionEvent, ScoreKind, motion_score};
+pub use extractor::{
+    ChannelDelays, ChannelModes, DEFAULT_FRAME_INTERVAL, DelayUnit, DiffMode, MotionExtractor, MotionMode,
+};
+pub use frame::{Frame, PixelFormat};
+pub use output::{FrameWriter, ImageFormat, SequenceWriter};
+pub use prefilter::{Prefilter, SpatialFilter};
+pub use roi::{Polygon, RoiMask, format_polygon, parse_polygons};
+pub use smooth::{Smoothing, TemporalFilter};
+pub use source::{
+    AutoControl, CameraControl, CameraSource, CaptureConfig, CaptureMode, ControlKind, FileInput, FrameSource,
+    ImageSequence, Pattern, PowerLine, SyntheticSource, capture_instant, closest_mode, list_devices,
+};
+pub use stats::{PipelineStats, Queue, Stage};
@@ -0,0 +1,124 @@
+use std::io;
+
+use motion_extraction::{CameraSource, ControlKind, PixelFormat, list_devices};
+
+pub fn print_devices() -> io::Result<()> {
+    for device in list_devices()? {
+        println!("{}", device.display());
+
+        let source = match CameraSource::open(&device.to_string_lossy()) {
+            Ok(source) => source,
+            Err(err) => {
+                println!("  unavailable: {}", err);
+                continue;
+            }
+        };
+
+        for info in source.camera().formats() {
+            let info = info?;
+            println!(
+                "  {}  {}{}",
+                fourcc(&info.format),
+                info.description,
+                if PixelFormat::from_fourcc(&info.format).is_none() {
+                    " (not supported)"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub fn print_modes(device: &str) -> io::Result<()> {
+    let source = CameraSource::open(device)?;
+    let modes = source.modes()?;
+
+    let mut format = None;
+    for mode in &modes {
+        if format != Some(mode.format) {
+            format = Some(mode.format);
+            println!("{}", fourcc(&mode.format));
+        }
+
+        let rates = mode
+            .intervals
+            .iter()
+            .map(|&interval| frame_rate(interval))
+            .collect::<Vec<_>>();
+        println!(
+            "  {}x{}  {} fps",
+            mode.resolution.0,
+            mode.resolution.1,
+            rates.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Lists the controls of the camera with their current values, or with `names` only those
+/// controls as `name=value` lines.
+pub fn print_controls(device: &str, names: &[String]) -> io::Result<()> {
+    let source = CameraSource::open(device)?;
+
+    if !names.is_empty() {
+        for name in names {
+            let control = source.control(name)?;
+            let value = control
+                .value
+                .map_or_else(|| "-".to_string(), |value| control.format_value(value));
+            println!("{}={}", control.key(), value);
+        }
+        return Ok(());
+    }
+
+    for control in source.controls()? {
+        let (kind, range) = match &control.kind {
+            ControlKind::Integer { minimum, maximum, step } => {
+                ("int", format!("  min={} max={} step={}", minimum, maximum, step))
+            }
+            ControlKind::Boolean => ("bool", String::new()),
+            ControlKind::Menu(_) => ("menu", String::new()),
+            ControlKind::Other => ("other", String::new()),
+        };
+
+        let mut line = format!("{} ({}){}", control.key(), kind, range);
+        if let Some(default) = control.default {
+            line += &format!(" default={}", control.format_value(default));
+        }
+        if let Some(value) = control.value {
+            line += &format!(" value={}", control.format_value(value));
+        }
+        if control.read_only {
+            line += " [read-only]";
+        }
+        if control.inactive {
+            line += " [inactive]";
+        }
+        println!("{}", line);
+
+        if let ControlKind::Menu(items) = &control.kind {
+            for (index, name) in items {
+                println!("    {}: {}", index, name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn fourcc(fourcc: &[u8; 4]) -> String {
+    String::from_utf8_lossy(fourcc).into_owned()
+}
+
+/// Formats the frame rate of a `(numerator, denominator)` interval, with decimals only if needed.
+pub fn frame_rate((numerator, denominator): (u32, u32)) -> String {
+    if numerator != 0 && denominator % numerator == 0 {
+        format!("{}", denominator / numerator)
+    } else {
+        format!("{:.2}", denominator as f64 / numerator.max(1) as f64)
+    }
+}
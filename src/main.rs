@@ -1,198 +1,383 @@
-use std::collections::VecDeque;
-use std::io::Cursor;
-use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
-use std::{mem, thread};
-
-use jpeg_decoder::Decoder as JpegDecoder;
+mod cli;
+mod controls;
+mod hud;
+mod list;
+mod roi_editor;
+mod signal;
+mod tile;
+
+use std::io::{self, ErrorKind, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+use std::{mem, process};
+
+use clap::{CommandFactory, Parser};
 use minifb::{Key, Scale, Window, WindowOptions};
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
-use rscam::{Camera, Config, Frame};
+use motion_extraction::{
+    CameraSource, EventLog, FileInput, FrameSource, FrameWriter, MotionEvent, Queue, Stage, SyntheticSource,
+};
 
-const WIDTH: usize = 1280;
-const HEIGHT: usize = 720;
+use cli::Args;
+use controls::Controls;
+use tile::{Tile, counted_channel, frames_in};
 
 const FS_WIDTH: usize = 1920;
 const FS_HEIGHT: usize = 1080;
 
-const FPS: u32 = 30;
+/// Length of the noise floor calibration started from the keyboard.
+const CALIBRATION_SECONDS: f64 = 2.0;
 
-const CHANNEL_OFFSET: usize = 4;
-const BUFFER_SIZE: usize = 2 + 2 * CHANNEL_OFFSET;
+/// Outline colour of the camera the keys apply to, while all cameras are shown.
+const SELECTED_COLOUR: u32 = 0xFFFF00;
 
-fn capture_thread(tx_capture: SyncSender<Frame>, rx_close: Receiver<()>) -> thread::JoinHandle<()> {
-    let config = Config {
-        interval: (1, FPS),
-        resolution: (WIDTH as u32, HEIGHT as u32),
-        format: b"MJPG",
-        ..Default::default()
-    };
+/// How often the window is redrawn while no frames arrive.
+const NO_SIGNAL_REFRESH: Duration = Duration::from_millis(100);
 
-    let mut cam = Camera::new("/dev/video0").expect("Failed to open camera");
-    cam.start(&config).expect("Failed to start camera");
+/// Length of the periods pipeline statistics are reported for.
+const STATS_PERIOD: Duration = Duration::from_secs(5);
 
-    thread::spawn(move || {
-        while rx_close.try_recv().is_err() {
-            match cam.capture() {
-                Ok(frame) => {
-                    if tx_capture.send(frame).is_err() {
-                        break;
-                    }
-                }
-                Err(err) => {
-                    eprintln!("Error capturing frame: {}", err);
-                    break;
-                }
-            }
-        }
-    })
+/// Where the `camera` with this index goes in a grid `columns` wide of `cell`s, as
+/// `(x, y, width, height)`.
+fn cell_rect(camera: usize, columns: usize, (cell_width, cell_height): (usize, usize)) -> (usize, usize, usize, usize) {
+    (
+        camera % columns * cell_width,
+        camera / columns * cell_height,
+        cell_width,
+        cell_height,
+    )
 }
 
-fn decode_thread(
-    rx_capture: Receiver<Frame>,
-    tx_decode: SyncSender<Vec<u32>>,
-    rx_close: Receiver<()>,
-) -> thread::JoinHandle<()> {
-    let mut decode_buf = Vec::with_capacity(WIDTH * HEIGHT * 3);
-    let mut decode_buf_u32 = Vec::with_capacity(WIDTH * HEIGHT);
-
-    thread::spawn(move || {
-        while rx_close.try_recv().is_err() {
-            let frame = match rx_capture.recv() {
-                Ok(frame) => frame,
-                Err(_) => break,
-            };
+/// Opens the camera and applies the controls given on the command line. Controls that can not be
+/// set are reported and skipped.
+fn open_camera(args: &Args, device: &str) -> CameraSource {
+    let mut camera = CameraSource::open(device).expect("Failed to open camera");
 
-            decode_buf.clear();
-            decode_buf.extend_from_slice(&frame);
+    // Locks come first, as manual values are ignored while the setting is automatic.
+    for &setting in &args.lock {
+        if let Err(err) = camera.lock(setting) {
+            eprintln!("Error locking {}: {}", setting.name(), err);
+        }
+    }
 
-            let mut decoder = JpegDecoder::new(Cursor::new(&decode_buf));
-            let pixels = decoder.decode().expect("Failed to decode JPEG");
+    if let Some(power_line) = args.power_line
+        && let Err(err) = camera.set_power_line(power_line)
+    {
+        eprintln!("Error setting power line frequency: {}", err);
+    }
 
-            decode_buf_u32.clear();
-            for (idx, chunk) in pixels.chunks_exact(3).enumerate() {
-                let r = chunk[0] as u32;
-                let g = chunk[1] as u32;
-                let b = chunk[2] as u32;
+    for (name, value) in &args.control {
+        let set = camera.control(name).and_then(|control| {
+            let value = control
+                .parse_value(value)
+                .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err))?;
+            camera.set_control(control.id, value)
+        });
 
-                let row = (idx + WIDTH - 1) / WIDTH + 1;
-                if decode_buf_u32.len() < row * WIDTH {
-                    decode_buf_u32.extend_from_slice(&vec![0u32; WIDTH]);
-                }
+        if let Err(err) = set {
+            eprintln!("Error setting control {}: {}", name, err);
+        }
+    }
 
-                let index = row * WIDTH - (idx % WIDTH) - 1;
-                decode_buf_u32[index] = (r << 16) | (g << 8) | b;
-            }
+    camera
+}
 
-            let pixels = mem::replace(&mut decode_buf_u32, Vec::with_capacity(WIDTH * HEIGHT));
-            if tx_decode.send(pixels).is_err() {
-                break;
-            }
-        }
-    })
+/// Logs `event`, naming the `source` it was seen by if there are several.
+fn log_event(log: &mut EventLog<impl Write>, event: &MotionEvent, source: Option<&str>) {
+    let written = match source {
+        Some(source) => log.write_from(event, source),
+        None => log.write(event),
+    };
+
+    if let Err(err) = written {
+        eprintln!("Error writing event log: {}", err);
+    }
+}
+
+fn open_window(title: &str, (width, height): (usize, usize), fullscreen: bool) -> Window {
+    Window::new(
+        title,
+        width,
+        height,
+        WindowOptions {
+            resize: !fullscreen,
+            borderless: fullscreen,
+            scale: Scale::FitScreen,
+            topmost: fullscreen,
+            ..Default::default()
+        },
+    )
+    .expect("Failed to create window")
 }
 
 fn main() {
-    let (tx_cap, rx_cap) = sync_channel(4);
-    let (tx_dec, rx_dec) = sync_channel(4);
+    let args = Args::parse();
+    if let Err(err) = args.check() {
+        Args::command()
+            .error(clap::error::ErrorKind::ValueValidation, err)
+            .exit();
+    }
+
+    if args.list_devices || args.list_modes || args.list_controls || !args.get_control.is_empty() {
+        let listed = if args.list_devices {
+            list::print_devices()
+        } else if args.list_modes {
+            list::print_modes(&args.device[0])
+        } else {
+            list::print_controls(&args.device[0], &args.get_control)
+        };
+
+        if let Err(err) = listed {
+            eprintln!("Error listing devices: {}", err);
+            process::exit(1);
+        }
 
-    let (tx_close_cap, rx_close_cap) = sync_channel(1);
-    let (tx_close_dec, rx_close_dec) = sync_channel(1);
+        return;
+    }
 
-    let cap_handle = capture_thread(tx_cap, rx_close_cap);
-    let dec_handle = decode_thread(rx_cap, tx_dec, rx_close_dec);
+    let sources: Vec<(String, Box<dyn FrameSource>)> = match (&args.input, args.pattern) {
+        (Some(path), _) => vec![(
+            path.display().to_string(),
+            Box::new(FileInput::open(path).expect("Failed to open input")),
+        )],
+        (None, Some(pattern)) => vec![(
+            "pattern".to_string(),
+            Box::new(SyntheticSource::open(pattern, args.seed)),
+        )],
+        (None, None) => args
+            .device
+            .iter()
+            .map(|device| {
+                let camera: Box<dyn FrameSource> = Box::new(open_camera(&args, device));
+                (device.clone(), camera)
+            })
+            .collect(),
+    };
 
-    let mut back_buffer = VecDeque::from(vec![vec![0; WIDTH * HEIGHT]; BUFFER_SIZE]);
+    // Live camera images are mirrored, recorded footage and test patterns are shown as they are.
+    let mirror = args.input.is_none() && args.pattern.is_none();
+    let mut controls = Controls::new(&args, mirror, sources.len());
+
+    // The decode threads of all cameras share the channel, tagging frames with their camera.
+    let (tx_dec, rx_dec) = counted_channel(4 * sources.len());
+    let mut tiles: Vec<Tile> = sources
+        .into_iter()
+        .enumerate()
+        .map(|(camera, (name, source))| Tile::start(camera, name, source, &args, &controls, tx_dec.clone()))
+        .collect();
+    drop(tx_dec);
+    let several = tiles.len() > 1;
+
+    let title = args.title(&tiles[0].config);
+
+    // Cameras are shown in the cells of a grid as close to square as can be, each the size of the
+    // first camera's frames.
+    let cell = (tiles[0].width, tiles[0].height);
+    let columns = (tiles.len() as f64).sqrt().ceil() as usize;
+    let rows = tiles.len().div_ceil(columns);
+    let (width, height) = (columns * cell.0, rows * cell.1);
+    let mut grid_buf = vec![0u32; width * height];
+    let mut output_buf = vec![0u32; width * height];
+
+    let mut event_log = args
+        .events
+        .as_ref()
+        .map(|path| EventLog::create(path).expect("Failed to open event log"));
+
+    let mut output = args.output.as_ref().map(|path| {
+        FrameWriter::create(
+            path,
+            args.image_format,
+            args.jpeg_quality,
+            (width, height),
+            tiles[0].interval,
+        )
+        .expect("Failed to create output")
+    });
+
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = Arc::clone(&running);
+    ctrlc::set_handler(move || handler_running.store(false, Ordering::Relaxed)).expect("Failed to set Ctrl-C handler");
 
     let mut fullscreen = false;
-    let mut dimensions = (WIDTH, HEIGHT);
+    let mut dimensions = (width, height);
     let mut position = (0, 0);
 
-    let mut diff_buf = vec![0u32; WIDTH * HEIGHT];
+    let mut window = (!args.headless).then(|| open_window(&title, dimensions, false));
+    let mut last_report = Instant::now();
 
-    let mut window = Window::new(
-        "Motion Extraction (720p30)",
-        WIDTH,
-        HEIGHT,
-        WindowOptions {
-            resize: true,
-            scale: Scale::FitScreen,
-            ..Default::default()
-        },
-    )
-    .expect("Failed to create window");
+    while running.load(Ordering::Relaxed) {
+        if let Some(win) = &mut window {
+            if !win.is_open() || win.is_key_released(Key::Escape) {
+                break;
+            }
 
-    while window.is_open() {
-        if window.is_key_released(Key::Escape) {
-            break;
-        }
+            if win.is_key_released(Key::F11) {
+                fullscreen = !fullscreen;
+
+                if fullscreen {
+                    dimensions = win.get_size();
+                    position = win.get_position();
+                }
 
-        if window.is_key_released(Key::F11) {
-            fullscreen = !fullscreen;
+                let size = if fullscreen { (FS_WIDTH, FS_HEIGHT) } else { dimensions };
+                *win = open_window(&title, size, fullscreen);
 
-            if fullscreen {
-                dimensions = window.get_size();
-                position = window.get_position();
+                if !fullscreen {
+                    win.set_position(position.0 - 4, position.1 - 46);
+                } else {
+                    win.set_cursor_visibility(false);
+                }
             }
 
-            window = Window::new(
-                "Motion Extraction (720p30)",
-                if fullscreen { FS_WIDTH } else { dimensions.0 },
-                if fullscreen { FS_HEIGHT } else { dimensions.1 },
-                WindowOptions {
-                    resize: !fullscreen,
-                    borderless: fullscreen,
-                    scale: Scale::FitScreen,
-                    topmost: fullscreen,
-                    ..Default::default()
-                },
-            )
-            .expect("Failed to create window");
-
-            if !fullscreen {
-                window.set_position(position.0 - 4, position.1 - 46);
-            } else {
-                window.set_cursor_visibility(false);
+            let changed = controls.update(win);
+            let tile = &mut tiles[controls.selected];
+
+            if changed {
+                controls.effects[controls.selected].apply(&mut tile.extractor);
+            }
+
+            if mem::take(&mut controls.calibrate) {
+                tile.extractor
+                    .calibrate_noise(frames_in(CALIBRATION_SECONDS, tile.interval));
+            }
+
+            if mem::take(&mut controls.clear_trails) {
+                tile.extractor.reset_trails();
+            }
+
+            // Areas can only be drawn where a click lands on a single camera.
+            if controls.shows_one() && tile.roi_editor.update(win) {
+                tile.extractor.set_roi(tile.roi_editor.roi.clone());
             }
         }
 
-        let curr = match rx_dec.recv() {
-            Ok(buf) => buf,
-            Err(_) => break,
-        };
+        let decoded = rx_dec.recv_timeout(NO_SIGNAL_REFRESH);
+        for tile in &mut tiles {
+            tile.monitor.update();
+        }
 
-        back_buffer.push_back(curr);
-        if back_buffer.len() > BUFFER_SIZE {
-            back_buffer.pop_front();
+        if args.stats && last_report.elapsed() >= STATS_PERIOD {
+            for tile in &mut tiles {
+                tile.report_stats(several);
+            }
+            last_report = Instant::now();
         }
 
-        let length = back_buffer.len();
-        let newest = &back_buffer[length - 1];
-        let frame_r = &back_buffer[length.saturating_sub(2)];
-        let frame_g = &back_buffer[length.saturating_sub(2 + CHANNEL_OFFSET)];
-        let frame_b = &back_buffer[length.saturating_sub(2 + CHANNEL_OFFSET + CHANNEL_OFFSET)];
+        // The camera and capture time of the frame that arrived, if one did.
+        let mut arrived = None;
 
-        diff_buf.par_iter_mut().enumerate().for_each(|(i, pixel)| {
-            let p = newest[i];
-            let dr = ((p >> 16) & 0xFF).saturating_sub((frame_r[i] >> 16) & 0xFF);
-            let dg = ((p >> 8) & 0xFF).saturating_sub((frame_g[i] >> 8) & 0xFF);
-            let db = (p & 0xFF).saturating_sub(frame_b[i] & 0xFF);
+        match decoded {
+            Ok((decoded, queued)) => {
+                let camera = decoded.camera;
+                arrived = Some((camera, decoded.captured));
+                let tile = &mut tiles[camera];
+                tile.stats.record_queue(Queue::Decoded, queued);
 
-            *pixel = (dr << 16) | (dg << 8) | db;
-        });
+                if let Some(event) = tile.process(decoded, &controls, &args)
+                    && let Some(log) = &mut event_log
+                {
+                    log_event(log, &event, several.then_some(tile.name.as_str()));
+                }
 
-        if let Err(err) = window.update_with_buffer(&diff_buf, WIDTH, HEIGHT) {
-            eprintln!("Error updating window: {}", err);
-            break;
+                // The output is written at the pace of the first camera.
+                if camera == 0
+                    && !controls.frozen
+                    && let Some(out) = &mut output
+                {
+                    let frame = if several {
+                        for (camera, tile) in tiles.iter().enumerate() {
+                            let rect = cell_rect(camera, columns, cell);
+                            hud::blit(
+                                &mut output_buf,
+                                width,
+                                rect,
+                                &tile.display_buf,
+                                (tile.width, tile.height),
+                            );
+                        }
+                        &output_buf
+                    } else {
+                        &tiles[0].display_buf
+                    };
+
+                    if let Err(err) = out.write_frame(frame, width, height) {
+                        eprintln!("Error writing output: {}", err);
+                        break;
+                    }
+                }
+            }
+            // Keeps the window responsive, showing that a camera is gone if one is.
+            Err(RecvTimeoutError::Timeout) => {
+                if tiles.iter().all(|tile| tile.monitor.lost_for().is_none()) {
+                    if let Some(win) = &mut window {
+                        win.update();
+                    }
+                    continue;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        if let Some(win) = &mut window {
+            let presenting = Instant::now();
+            let result = if controls.shows_one() {
+                let tile = &mut tiles[controls.selected];
+                let (tile_width, tile_height) = (tile.width, tile.height);
+                win.update_with_buffer(tile.render(&controls, controls.selected), tile_width, tile_height)
+            } else {
+                for (camera, tile) in tiles.iter_mut().enumerate() {
+                    let size = (tile.width, tile.height);
+                    hud::blit(
+                        &mut grid_buf,
+                        width,
+                        cell_rect(camera, columns, cell),
+                        tile.render(&controls, camera),
+                        size,
+                    );
+                }
+
+                let thickness = (height / 360).max(1);
+                let rect = cell_rect(controls.selected, columns, cell);
+                hud::draw_rect(&mut grid_buf, width, height, rect, thickness, SELECTED_COLOUR);
+
+                win.update_with_buffer(&grid_buf, width, height)
+            };
+
+            if let Err(err) = result {
+                eprintln!("Error updating window: {}", err);
+                break;
+            }
+
+            if let Some((camera, _)) = arrived {
+                tiles[camera].stats.record_stage(Stage::Present, presenting.elapsed());
+            }
+        }
+
+        if let Some((camera, captured)) = arrived {
+            tiles[camera].stats.record_latency(captured.elapsed());
         }
     }
 
-    tx_close_cap
-        .send(())
-        .expect("Failed to send close signal to capture thread");
-    tx_close_dec
-        .send(())
-        .expect("Failed to send close signal to decode thread");
+    for tile in &mut tiles {
+        if let Some(event) = tile.finish()
+            && let Some(log) = &mut event_log
+        {
+            log_event(log, &event, several.then_some(tile.name.as_str()));
+        }
+    }
+
+    if let Some(out) = output
+        && let Err(err) = out.finish()
+    {
+        eprintln!("Error finishing output: {}", err);
+    }
 
-    cap_handle.join().expect("Failed to join capture thread");
-    dec_handle.join().expect("Failed to join decode thread");
+    // Unblocks the decode threads if they are waiting for room in the channel.
+    drop(rx_dec);
+
+    for tile in tiles {
+        tile.join();
+    }
 }
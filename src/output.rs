@@ -0,0 +1,128 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::avi::AviWriter;
+use crate::encode;
+
+/// Image format of the files written by a [`SequenceWriter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Ppm => "ppm",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "ppm" => Ok(ImageFormat::Ppm),
+            _ => Err(format!("unknown image format {:?}, expected png or ppm", s)),
+        }
+    }
+}
+
+/// Writes every frame to its own numbered image file in a directory.
+pub struct SequenceWriter {
+    dir: PathBuf,
+    format: ImageFormat,
+    next: usize,
+}
+
+impl SequenceWriter {
+    /// Creates `dir` if it does not exist yet. Existing files with clashing names are overwritten.
+    pub fn create(dir: impl AsRef<Path>, format: ImageFormat) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            format,
+            next: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, pixels: &[u32], width: usize, height: usize) -> io::Result<()> {
+        let path = self.dir.join(format!("{:06}.{}", self.next, self.format.extension()));
+        let file = BufWriter::new(File::create(path)?);
+
+        match self.format {
+            ImageFormat::Png => encode::encode_png(pixels, width, height, file)?,
+            ImageFormat::Ppm => encode::encode_ppm(pixels, width, height, file)?,
+        }
+
+        self.next += 1;
+        Ok(())
+    }
+}
+
+/// Destination for processed `0RGB` frames when they are not, or not only, shown on screen.
+pub enum FrameWriter {
+    Sequence(SequenceWriter),
+    Avi {
+        writer: AviWriter<BufWriter<File>>,
+        quality: u8,
+    },
+}
+
+impl FrameWriter {
+    /// Writes a Motion-JPEG AVI file if `path` ends in `.avi` and a numbered `format` image
+    /// sequence into the directory `path` otherwise.
+    pub fn create(
+        path: impl AsRef<Path>,
+        format: ImageFormat,
+        quality: u8,
+        (width, height): (usize, usize),
+        frame_interval: Duration,
+    ) -> io::Result<Self> {
+        let path = path.as_ref();
+        let is_avi = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("avi"));
+
+        if is_avi {
+            let writer = AviWriter::create(path, width, height, frame_interval)?;
+            Ok(FrameWriter::Avi { writer, quality })
+        } else {
+            SequenceWriter::create(path, format).map(FrameWriter::Sequence)
+        }
+    }
+
+    pub fn write_frame(&mut self, pixels: &[u32], width: usize, height: usize) -> io::Result<()> {
+        match self {
+            FrameWriter::Sequence(sequence) => sequence.write_frame(pixels, width, height),
+            FrameWriter::Avi { writer, quality } => {
+                if (width, height) != (writer.width(), writer.height()) {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "frame size differs from AVI size",
+                    ));
+                }
+
+                let mut jpeg = Vec::new();
+                encode::encode_jpeg(pixels, width, height, *quality, &mut jpeg)?;
+                writer.write_jpeg(&jpeg)
+            }
+        }
+    }
+
+    /// Completes the output. AVI files are unreadable until this has been called.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            FrameWriter::Sequence(_) => Ok(()),
+            FrameWriter::Avi { writer, .. } => writer.finish().map(drop),
+        }
+    }
+}
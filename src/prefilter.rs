@@ -0,0 +1,205 @@
+use std::str::FromStr;
+
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+use rayon::slice::ParallelSliceMut;
+
+/// Spatial filtering of frames before they are diffed, which evens out camera shake and
+/// compression noise along edges.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Prefilter {
+    #[default]
+    Off,
+    /// Gaussian blur with the given standard deviation in pixels.
+    Gaussian(f32),
+    /// Mean over a square of the given radius in pixels.
+    Box(usize),
+    /// Averages blocks of the given size into single pixels, shrinking the frame by that factor
+    /// in both directions. Cheaper than blurring and makes every later stage cheaper as well.
+    Downscale(usize),
+}
+
+impl Prefilter {
+    /// Size of the frames the filter turns frames of `width` by `height` pixels into.
+    pub fn output_size(&self, (width, height): (usize, usize)) -> (usize, usize) {
+        match *self {
+            Prefilter::Downscale(factor) => ((width / factor).max(1), (height / factor).max(1)),
+            _ => (width, height),
+        }
+    }
+}
+
+impl FromStr for Prefilter {
+    type Err = String;
+
+    /// Parses `off`, `gaussian:SIGMA`, `box:RADIUS` or `downscale:FACTOR`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+
+        match s.split_once(':') {
+            None if s == "off" => Ok(Prefilter::Off),
+            Some(("gaussian", sigma)) => match sigma.parse::<f32>() {
+                Ok(sigma) if sigma > 0.0 && sigma <= 20.0 => Ok(Prefilter::Gaussian(sigma)),
+                _ => Err(format!(
+                    "Gaussian sigma must be above 0 and at most 20, got {:?}",
+                    sigma
+                )),
+            },
+            Some(("box", radius)) => match radius.parse::<usize>() {
+                Ok(radius) if (1..=50).contains(&radius) => Ok(Prefilter::Box(radius)),
+                _ => Err(format!("box radius must be 1 to 50 pixels, got {:?}", radius)),
+            },
+            Some(("downscale", factor)) => match factor.parse::<usize>() {
+                Ok(factor) if (1..=16).contains(&factor) => Ok(Prefilter::Downscale(factor)),
+                _ => Err(format!("downscale factor must be 1 to 16, got {:?}", factor)),
+            },
+            _ => Err(format!(
+                "unknown prefilter {:?}, expected off, gaussian:SIGMA, box:RADIUS or downscale:FACTOR",
+                s
+            )),
+        }
+    }
+}
+
+/// Applies a [`Prefilter`] to `0RGB` frames of a fixed size.
+pub struct SpatialFilter {
+    prefilter: Prefilter,
+    width: usize,
+    height: usize,
+    /// Weights of the blur kernel from its centre outwards.
+    weights: Vec<u32>,
+    scratch: Vec<u32>,
+}
+
+impl SpatialFilter {
+    pub fn new(prefilter: Prefilter, (width, height): (usize, usize)) -> Self {
+        let weights = match prefilter {
+            Prefilter::Gaussian(sigma) => {
+                let radius = (3.0 * sigma).ceil() as usize;
+                (0..=radius)
+                    .map(|d| (1024.0 * (-((d * d) as f32) / (2.0 * sigma * sigma)).exp()).round() as u32)
+                    .collect()
+            }
+            Prefilter::Box(radius) => vec![1; radius + 1],
+            _ => Vec::new(),
+        };
+
+        // Blurs keep the result of the horizontal pass for the vertical one.
+        let scratch = if weights.is_empty() {
+            Vec::new()
+        } else {
+            vec![0; width * height]
+        };
+
+        Self {
+            prefilter,
+            width,
+            height,
+            weights,
+            scratch,
+        }
+    }
+
+    pub fn prefilter(&self) -> Prefilter {
+        self.prefilter
+    }
+
+    pub fn output_size(&self) -> (usize, usize) {
+        self.prefilter.output_size((self.width, self.height))
+    }
+
+    /// Filters `frame` into `out`, which has to be [`output_size`](Self::output_size).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame` or `out` have the wrong size.
+    pub fn apply(&mut self, frame: &[u32], out: &mut [u32]) {
+        let (out_width, out_height) = self.output_size();
+        assert_eq!(
+            frame.len(),
+            self.width * self.height,
+            "Frame size does not match filter"
+        );
+        assert_eq!(out.len(), out_width * out_height, "Output size does not match filter");
+
+        match self.prefilter {
+            Prefilter::Off => out.copy_from_slice(frame),
+            Prefilter::Gaussian(_) | Prefilter::Box(_) => {
+                let (width, height) = (self.width, self.height);
+                let weights = &self.weights;
+
+                self.scratch
+                    .par_chunks_exact_mut(width)
+                    .enumerate()
+                    .for_each(|(y, row)| {
+                        let source = &frame[y * width..(y + 1) * width];
+                        for (x, pixel) in row.iter_mut().enumerate() {
+                            *pixel = convolve(weights, |offset| source[clamp(x, offset, width)]);
+                        }
+                    });
+
+                let scratch = &self.scratch;
+                out.par_chunks_exact_mut(width).enumerate().for_each(|(y, row)| {
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        *pixel = convolve(weights, |offset| scratch[clamp(y, offset, height) * width + x]);
+                    }
+                });
+            }
+            Prefilter::Downscale(factor) => {
+                let width = self.width;
+
+                // Frames smaller than a block shrink it to the pixels there are.
+                out.par_chunks_exact_mut(out_width).enumerate().for_each(|(y, row)| {
+                    for (x, pixel) in row.iter_mut().enumerate() {
+                        let columns = x * factor..((x + 1) * factor).min(width);
+                        let mut sum = [0; 3];
+                        let mut count = 0;
+                        for source in frame[y * factor * width..].chunks(width).take(factor) {
+                            for &p in &source[columns.clone()] {
+                                add(&mut sum, p, 1);
+                                count += 1;
+                            }
+                        }
+                        *pixel = pack(sum, count);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Weighted sum of the pixels around a centre, where `pixel` looks up the pixel at a signed
+/// offset from it.
+#[inline]
+fn convolve(weights: &[u32], pixel: impl Fn(isize) -> u32) -> u32 {
+    let mut sum = [0; 3];
+    let mut total = weights[0];
+    add(&mut sum, pixel(0), weights[0]);
+
+    for (d, &weight) in weights.iter().enumerate().skip(1) {
+        add(&mut sum, pixel(d as isize), weight);
+        add(&mut sum, pixel(-(d as isize)), weight);
+        total += 2 * weight;
+    }
+
+    pack(sum, total)
+}
+
+/// `index + offset`, with indices outside `0..len` repeating the edge.
+#[inline]
+fn clamp(index: usize, offset: isize, len: usize) -> usize {
+    index.saturating_add_signed(offset).min(len - 1)
+}
+
+#[inline]
+fn add(sum: &mut [u32; 3], pixel: u32, weight: u32) {
+    sum[0] += ((pixel >> 16) & 0xFF) * weight;
+    sum[1] += ((pixel >> 8) & 0xFF) * weight;
+    sum[2] += (pixel & 0xFF) * weight;
+}
+
+/// Divides the channel sums by `total` and packs them into a `0RGB` pixel.
+#[inline]
+fn pack(sum: [u32; 3], total: u32) -> u32 {
+    let channel = |sum: u32| (sum + total / 2) / total;
+    (channel(sum[0]) << 16) | (channel(sum[1]) << 8) | channel(sum[2])
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::decode;
+use crate::frame::{Frame, PixelFormat};
+
+/// A polygon as a list of `(x, y)` corners in pixels.
+pub type Polygon = Vec<(f64, f64)>;
+
+/// Marks which pixels of a frame motion is extracted from. Pixels outside the region of interest
+/// show no motion and are left out of motion statistics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoiMask {
+    width: usize,
+    height: usize,
+    /// `1` for pixels inside the region of interest and `0` elsewhere.
+    mask: Vec<u8>,
+}
+
+impl RoiMask {
+    /// A mask that includes every pixel.
+    pub fn full(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            mask: vec![1; width * height],
+        }
+    }
+
+    /// Loads a PNG image in which bright pixels are inside the region and dark pixels outside it.
+    /// Images of a different size are stretched to `width` by `height`.
+    pub fn from_png(path: impl AsRef<Path>, width: usize, height: usize) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let to_io_error = |err| io::Error::new(ErrorKind::InvalidData, err);
+
+        let (image_width, image_height) = decode::probe(PixelFormat::Png, &data).map_err(to_io_error)?;
+        let frame = Frame {
+            format: PixelFormat::Png,
+            width: image_width,
+            height: image_height,
+            data,
+            timestamp: Duration::ZERO,
+            sequence: 0,
+        };
+        let mut pixels = vec![0; image_width * image_height];
+        decode::decode(&frame, image_width, image_height, &mut pixels).map_err(to_io_error)?;
+
+        let mut mask = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = y * image_height / height * image_width;
+            for x in 0..width {
+                let pixel = pixels[row + x * image_width / width];
+                let brightness = ((pixel >> 16) & 0xFF) + ((pixel >> 8) & 0xFF) + (pixel & 0xFF);
+                mask.push((brightness >= 3 * 128) as u8);
+            }
+        }
+
+        Ok(Self { width, height, mask })
+    }
+
+    /// Builds a mask from the area inside any of `polygons`.
+    pub fn from_polygons(polygons: &[Polygon], width: usize, height: usize) -> Self {
+        let mut roi = Self {
+            width,
+            height,
+            mask: vec![0; width * height],
+        };
+
+        for polygon in polygons {
+            roi.fill_polygon(polygon, 1);
+        }
+
+        roi
+    }
+
+    /// Loads a polygon list, see [`parse_polygons`].
+    pub fn load_polygons(path: impl AsRef<Path>, width: usize, height: usize) -> io::Result<Self> {
+        let polygons =
+            parse_polygons(&fs::read_to_string(path)?).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        Ok(Self::from_polygons(&polygons, width, height))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// `1` for every pixel inside the region and `0` for every pixel outside it.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.mask
+    }
+
+    /// Number of pixels inside the region.
+    pub fn area(&self) -> usize {
+        self.mask.iter().filter(|&&included| included != 0).count()
+    }
+
+    /// Swaps the inside and outside of the region, turning a region of interest into an
+    /// exclusion mask and back.
+    pub fn invert(&mut self) {
+        self.mask.iter_mut().for_each(|included| *included ^= 1);
+    }
+
+    /// Removes the area inside `polygon` from the region.
+    pub fn exclude_polygon(&mut self, polygon: &[(f64, f64)]) {
+        self.fill_polygon(polygon, 0);
+    }
+
+    /// Sets every pixel whose centre lies inside `polygon` to `value`, using the even-odd rule.
+    fn fill_polygon(&mut self, polygon: &[(f64, f64)], value: u8) {
+        if polygon.len() < 3 {
+            return;
+        }
+
+        let mut crossings = Vec::new();
+        for y in 0..self.height {
+            let cy = y as f64 + 0.5;
+
+            crossings.clear();
+            for (i, &(x0, y0)) in polygon.iter().enumerate() {
+                let (x1, y1) = polygon[(i + 1) % polygon.len()];
+                if (y0 <= cy) != (y1 <= cy) {
+                    crossings.push(x0 + (cy - y0) / (y1 - y0) * (x1 - x0));
+                }
+            }
+            crossings.sort_by(f64::total_cmp);
+
+            let row = &mut self.mask[y * self.width..(y + 1) * self.width];
+            for span in crossings.chunks_exact(2) {
+                // Pixels whose centre lies within the span.
+                let start = (span[0] - 0.5).ceil().clamp(0.0, self.width as f64) as usize;
+                let end = (span[1] - 0.5).ceil().clamp(0.0, self.width as f64) as usize;
+                row[start..end.max(start)].fill(value);
+            }
+        }
+    }
+}
+
+/// Parses a polygon list: one polygon per line, written as space-separated `x,y` corners in
+/// pixels. Empty lines and lines starting with `#` are skipped.
+pub fn parse_polygons(text: &str) -> Result<Vec<Polygon>, String> {
+    let mut polygons = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let polygon = line
+            .split_whitespace()
+            .map(|corner| {
+                let (x, y) = corner.split_once(',')?;
+                Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+            })
+            .collect::<Option<Polygon>>()
+            .ok_or_else(|| format!("line {}: expected corners like 10,20", number + 1))?;
+
+        if polygon.len() < 3 {
+            return Err(format!("line {}: a polygon needs at least three corners", number + 1));
+        }
+
+        polygons.push(polygon);
+    }
+
+    Ok(polygons)
+}
+
+/// Formats `polygon` as a line of a polygon list, see [`parse_polygons`].
+pub fn format_polygon(polygon: &[(f64, f64)]) -> String {
+    polygon
+        .iter()
+        .map(|(x, y)| format!("{},{}", x.round(), y.round()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
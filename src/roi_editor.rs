@@ -0,0 +1,91 @@
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window};
+use motion_extraction::{Polygon, RoiMask, format_polygon};
+
+/// Draws areas to ignore into the region of interest with the mouse.
+pub struct RoiEditor {
+    width: usize,
+    height: usize,
+    /// Size of the frames before they were prefiltered, which areas are printed in.
+    capture_size: (usize, usize),
+    /// The mask given on the command line, if any.
+    loaded: Option<RoiMask>,
+    pub roi: Option<RoiMask>,
+    /// Corners of the area being drawn, in frame pixels.
+    points: Polygon,
+    left_down: bool,
+    right_down: bool,
+}
+
+impl RoiEditor {
+    pub fn new((width, height): (usize, usize), capture_size: (usize, usize), loaded: Option<RoiMask>) -> Self {
+        Self {
+            width,
+            height,
+            capture_size,
+            roi: loaded.clone(),
+            loaded,
+            points: Vec::new(),
+            left_down: false,
+            right_down: false,
+        }
+    }
+
+    /// Corners of the area being drawn.
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// Applies the clicks and keys since the last call. Returns whether the mask changed.
+    pub fn update(&mut self, window: &Window) -> bool {
+        let left_down = window.get_mouse_down(MouseButton::Left);
+        let right_down = window.get_mouse_down(MouseButton::Right);
+        let (left_clicked, right_clicked) = (left_down && !self.left_down, right_down && !self.right_down);
+        (self.left_down, self.right_down) = (left_down, right_down);
+
+        // The frame is stretched over the whole window.
+        if left_clicked && let Some((x, y)) = window.get_unscaled_mouse_pos(MouseMode::Discard) {
+            let (window_width, window_height) = window.get_size();
+            self.points.push((
+                x as f64 * self.width as f64 / window_width.max(1) as f64,
+                y as f64 * self.height as f64 / window_height.max(1) as f64,
+            ));
+        }
+
+        if right_clicked || window.is_key_pressed(Key::Enter, KeyRepeat::No) {
+            return self.close();
+        }
+
+        if window.is_key_pressed(Key::Backspace, KeyRepeat::No) {
+            if self.points.pop().is_some() {
+                return false;
+            }
+
+            let changed = self.roi != self.loaded;
+            self.roi = self.loaded.clone();
+            return changed;
+        }
+
+        false
+    }
+
+    /// Removes the area drawn so far from the mask.
+    fn close(&mut self) -> bool {
+        let polygon = std::mem::take(&mut self.points);
+        if polygon.len() < 3 {
+            return false;
+        }
+
+        // Printed in the format --roi reads, for use with --invert-roi.
+        let scale = (
+            self.capture_size.0 as f64 / self.width as f64,
+            self.capture_size.1 as f64 / self.height as f64,
+        );
+        let captured: Polygon = polygon.iter().map(|&(x, y)| (x * scale.0, y * scale.1)).collect();
+        eprintln!("Ignoring area {}", format_polygon(&captured));
+
+        self.roi
+            .get_or_insert_with(|| RoiMask::full(self.width, self.height))
+            .exclude_polygon(&polygon);
+        true
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Keeps count of the times the capture thread lost its camera.
+pub struct SignalMonitor {
+    /// Shared with the capture thread, which clears it while it reconnects.
+    pub signal: Arc<AtomicBool>,
+    lost_since: Option<Instant>,
+    outages: usize,
+    downtime: Duration,
+}
+
+impl SignalMonitor {
+    pub fn new() -> Self {
+        Self {
+            signal: Arc::new(AtomicBool::new(true)),
+            lost_since: None,
+            outages: 0,
+            downtime: Duration::ZERO,
+        }
+    }
+
+    /// Picks up whether the signal was lost or came back since the last call.
+    pub fn update(&mut self) {
+        let present = self.signal.load(Ordering::Relaxed);
+
+        match self.lost_since {
+            None if !present => {
+                self.lost_since = Some(Instant::now());
+                self.outages += 1;
+            }
+            Some(since) if present => {
+                self.downtime += since.elapsed();
+                self.lost_since = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// How long the signal has been gone, if it is.
+    pub fn lost_for(&self) -> Option<Duration> {
+        self.lost_since.map(|since| since.elapsed())
+    }
+
+    pub fn outages(&self) -> usize {
+        self.outages
+    }
+
+    /// Total time without signal, including an outage still going on.
+    pub fn downtime(&self) -> Duration {
+        self.downtime + self.lost_for().unwrap_or_default()
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+
+/// Averaging of consecutive output frames, which lets faint but steady motion stand out from
+/// noise that flickers from frame to frame.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Smoothing {
+    #[default]
+    Off,
+    /// Exponential moving average, where every new frame is weighted by the given factor
+    /// between `0` and `1`.
+    Ema(f32),
+    /// Mean of the given number of most recent frames.
+    Box(usize),
+}
+
+impl FromStr for Smoothing {
+    type Err = String;
+
+    /// Parses `off`, `ema:WEIGHT` or `box:FRAMES`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_ascii_lowercase();
+        let invalid = || format!("unknown smoothing {:?}, expected off, ema:WEIGHT or box:FRAMES", s);
+
+        match s.split_once(':') {
+            None if s == "off" => Ok(Smoothing::Off),
+            Some(("ema", weight)) => match weight.parse::<f32>() {
+                Ok(weight) if weight > 0.0 && weight <= 1.0 => Ok(Smoothing::Ema(weight)),
+                _ => Err(format!("EMA weight must be above 0 and at most 1, got {:?}", weight)),
+            },
+            Some(("box", frames)) => match frames.parse::<usize>() {
+                Ok(frames) if (1..=255).contains(&frames) => Ok(Smoothing::Box(frames)),
+                _ => Err(format!("box length must be 1 to 255 frames, got {:?}", frames)),
+            },
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Applies [`Smoothing`] to a stream of `0RGB` frames, channel by channel.
+pub struct TemporalFilter {
+    smoothing: Smoothing,
+    /// Running average of every channel, for [`Smoothing::Ema`].
+    average: Vec<[f32; 3]>,
+    /// The frames in the box and the sum of every channel over them, for [`Smoothing::Box`].
+    frames: VecDeque<Vec<u32>>,
+    sums: Vec<[u32; 3]>,
+}
+
+impl TemporalFilter {
+    pub fn new(smoothing: Smoothing) -> Self {
+        Self {
+            smoothing,
+            average: Vec::new(),
+            frames: VecDeque::new(),
+            sums: Vec::new(),
+        }
+    }
+
+    pub fn smoothing(&self) -> Smoothing {
+        self.smoothing
+    }
+
+    /// Replaces `frame` with its average over the recent frames. The first frame after creating
+    /// or [`reset`](Self::reset)ting the filter passes unchanged.
+    pub fn apply(&mut self, frame: &mut [u32]) {
+        match self.smoothing {
+            Smoothing::Off => {}
+            Smoothing::Ema(weight) => {
+                if self.average.len() != frame.len() {
+                    self.average = frame.iter().map(|&pixel| channels(pixel).map(|c| c as f32)).collect();
+                    return;
+                }
+
+                frame
+                    .par_iter_mut()
+                    .zip(&mut self.average)
+                    .for_each(|(pixel, average)| {
+                        for (average, channel) in average.iter_mut().zip(channels(*pixel)) {
+                            *average += (channel as f32 - *average) * weight;
+                        }
+                        *pixel = pack(average.map(|c| c.round() as u32));
+                    });
+            }
+            Smoothing::Box(len) => {
+                if self.sums.len() != frame.len() {
+                    self.frames.clear();
+                    self.sums = vec![[0; 3]; frame.len()];
+                }
+
+                // Reuses the allocation of the frame that leaves the box.
+                let mut entering = if self.frames.len() == len {
+                    let leaving = self.frames.pop_front().expect("Box is full");
+                    self.sums.par_iter_mut().zip(&leaving).for_each(|(sum, &pixel)| {
+                        for (sum, channel) in sum.iter_mut().zip(channels(pixel)) {
+                            *sum -= channel;
+                        }
+                    });
+                    leaving
+                } else {
+                    vec![0; frame.len()]
+                };
+
+                entering.copy_from_slice(frame);
+                self.frames.push_back(entering);
+
+                let count = self.frames.len() as u32;
+                frame.par_iter_mut().zip(&mut self.sums).for_each(|(pixel, sum)| {
+                    for (sum, channel) in sum.iter_mut().zip(channels(*pixel)) {
+                        *sum += channel;
+                    }
+                    *pixel = pack(sum.map(|sum| (sum + count / 2) / count));
+                });
+            }
+        }
+    }
+
+    /// Forgets the frames seen so far.
+    pub fn reset(&mut self) {
+        self.average.clear();
+        self.frames.clear();
+        self.sums.clear();
+    }
+}
+
+fn channels(pixel: u32) -> [u32; 3] {
+    [(pixel >> 16) & 0xFF, (pixel >> 8) & 0xFF, pixel & 0xFF]
+}
+
+fn pack([r, g, b]: [u32; 3]) -> u32 {
+    (r << 16) | (g << 8) | b
+}
@@ -0,0 +1,61 @@
+mod camera;
+mod controls;
+mod file;
+mod synthetic;
+
+use std::io;
+use std::time::Duration;
+
+use crate::frame::Frame;
+
+pub use camera::{CameraSource, CaptureMode, capture_instant, closest_mode, list_devices};
+pub use controls::{AutoControl, CameraControl, ControlKind, PowerLine};
+pub use file::{FileInput, ImageSequence};
+pub use synthetic::{Pattern, SyntheticSource};
+
+/// Capture mode requested from, or reported by, a [`FrameSource`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureConfig {
+    pub resolution: (usize, usize),
+    /// Time between two frames in seconds, as a numerator and denominator pair like V4L2 uses.
+    pub interval: (u32, u32),
+    /// FourCC code of the pixel format. Left out of a request, the source picks one it can
+    /// deliver; sources always report the format they picked.
+    pub format: Option<[u8; 4]>,
+}
+
+impl CaptureConfig {
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs(self.interval.0 as u64) / self.interval.1.max(1)
+    }
+}
+
+/// Anything that can deliver undecoded frames: cameras, recorded footage, generators, streams.
+///
+/// Sources are created by their own `open` constructors, then [`configure`](Self::configure)d
+/// before the first frame is requested.
+pub trait FrameSource: Send {
+    /// Asks the source for a capture mode and returns the mode it actually delivers. Sources that
+    /// can not be configured, like files, ignore the request and describe their content instead.
+    fn configure(&mut self, config: &CaptureConfig) -> io::Result<CaptureConfig>;
+
+    /// Blocks until the next frame is available. Returns `None` once the source is exhausted.
+    fn next_frame(&mut self) -> io::Result<Option<Frame>>;
+
+    /// Opens the source again after it failed, e.g. a camera that was unplugged, and configures
+    /// it like [`configure`](Self::configure). Sources that can not be reopened return an error
+    /// of kind [`Unsupported`](io::ErrorKind::Unsupported).
+    fn reopen(&mut self, config: &CaptureConfig) -> io::Result<CaptureConfig> {
+        let _ = config;
+        Err(io::Error::new(io::ErrorKind::Unsupported, "source can not be reopened"))
+    }
+
+    /// Stops delivering frames and releases the underlying device.
+    fn close(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Whether the source delivers frames in real time. Sources that do not are played back at
+    /// the configured frame interval by the consumer, if at all.
+    fn is_live(&self) -> bool;
+}
@@ -0,0 +1,442 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use rscam::{Camera, Config, IntervalInfo, ResolutionInfo};
+
+use super::controls::{AutoControl, CameraControl, PowerLine};
+use super::{CaptureConfig, FrameSource};
+use crate::frame::{Frame, PixelFormat};
+
+/// Decodable formats in the order they are negotiated. Raw formats come first because they skip
+/// JPEG decoding, grey comes last because it loses the colour the effect is built on.
+const PREFERRED_FORMATS: [PixelFormat; 5] = [
+    PixelFormat::Yuyv,
+    PixelFormat::Nv12,
+    PixelFormat::Rgb24,
+    PixelFormat::Mjpeg,
+    PixelFormat::Grey,
+];
+
+/// Resolutions tried within a continuous range of sizes when listing modes.
+const COMMON_RESOLUTIONS: [(u32, u32); 11] = [
+    (160, 120),
+    (320, 240),
+    (640, 480),
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1280, 960),
+    (1600, 1200),
+    (1920, 1080),
+    (2560, 1440),
+    (3840, 2160),
+];
+
+/// Frame rates tried within a continuous range of intervals when listing modes.
+const COMMON_FRAME_RATES: [u32; 11] = [5, 10, 15, 20, 24, 25, 30, 50, 60, 90, 120];
+
+/// A V4L2 capture device.
+///
+/// Controls changed through the source are put back the way they were when it is dropped.
+pub struct CameraSource {
+    device: String,
+    camera: Camera,
+    streaming: bool,
+    /// Whether the camera was stopped. rscam only starts idle cameras, a stopped one has to be
+    /// opened again first.
+    stopped: bool,
+    /// Controls changed so far with the values they had before, in the order they were changed.
+    saved_controls: Vec<(u32, i64)>,
+    /// Controls changed so far with the values they were changed to, applied again on
+    /// [`reopen`](FrameSource::reopen).
+    applied_controls: Vec<(u32, i64)>,
+    /// Time between two frames in the configured mode.
+    interval: Duration,
+    /// Timestamp and sequence number of the last frame. rscam does not hand out the sequence
+    /// numbers of V4L2 buffers, so they are counted from the gaps between timestamps instead.
+    last_frame: Option<(Duration, u64)>,
+}
+
+impl CameraSource {
+    pub fn open(device: &str) -> io::Result<Self> {
+        Ok(Self {
+            device: device.to_string(),
+            camera: Camera::new(device)?,
+            streaming: false,
+            stopped: false,
+            saved_controls: Vec::new(),
+            applied_controls: Vec::new(),
+            interval: Duration::ZERO,
+            last_frame: None,
+        })
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Lists the controls the camera offers.
+    pub fn controls(&self) -> io::Result<Vec<CameraControl>> {
+        let mut controls = Vec::new();
+        for control in self.camera.controls() {
+            controls.extend(CameraControl::from_rscam(control?));
+        }
+        Ok(controls)
+    }
+
+    /// Looks up a control by its [`key`](CameraControl::key) or its name.
+    pub fn control(&self, name: &str) -> io::Result<CameraControl> {
+        self.controls()?
+            .into_iter()
+            .find(|control| control.is_named(name))
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, format!("camera has no control {:?}", name)))
+    }
+
+    /// Sets control `id` to `value`, saving the value it had first so that it can be restored.
+    pub fn set_control(&mut self, id: u32, value: i64) -> io::Result<()> {
+        if !self.saved_controls.iter().any(|&(saved, _)| saved == id) {
+            let current = CameraControl::from_rscam(self.camera.get_control(id)?).and_then(|control| control.value);
+            if let Some(current) = current {
+                self.saved_controls.push((id, current));
+            }
+        }
+
+        self.camera.set_control(id, &value)?;
+
+        self.applied_controls.retain(|&(applied, _)| applied != id);
+        self.applied_controls.push((id, value));
+        Ok(())
+    }
+
+    /// Stops the camera from adjusting `setting` by itself, which keeps its current value.
+    pub fn lock(&mut self, setting: AutoControl) -> io::Result<()> {
+        let (id, manual) = setting.manual_setting();
+        self.set_control(id, manual)
+    }
+
+    pub fn set_power_line(&mut self, power_line: PowerLine) -> io::Result<()> {
+        self.set_control(rscam::CID_POWER_LINE_FREQUENCY, power_line.value())
+    }
+
+    /// Puts every control changed so far back the way it was, in reverse order so that manual
+    /// values are restored before the automatic settings they depend on.
+    pub fn restore_controls(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        while let Some((id, value)) = self.saved_controls.pop() {
+            if let Err(err) = self.camera.set_control(id, &value) {
+                result = Err(err);
+            }
+        }
+        result
+    }
+
+    /// Enumerates the resolutions and frame intervals the camera offers in every pixel format.
+    ///
+    /// Drivers that describe a continuous range instead of discrete values are sampled at common
+    /// resolutions and frame rates within that range, plus its bounds.
+    pub fn modes(&self) -> io::Result<Vec<CaptureMode>> {
+        let mut modes = Vec::new();
+
+        for info in self.camera.formats() {
+            let format = info?.format;
+            let resolutions = match self.camera.resolutions(&format) {
+                Ok(ResolutionInfo::Discretes(sizes)) => sizes,
+                Ok(ResolutionInfo::Stepwise { min, max, step }) => {
+                    let mut sizes = vec![min];
+                    sizes.extend(
+                        COMMON_RESOLUTIONS
+                            .iter()
+                            .filter(|&&size| size != min && size != max && fits_stepwise(size, min, max, step)),
+                    );
+                    sizes.push(max);
+                    sizes
+                }
+                Err(_) => continue,
+            };
+
+            for resolution in resolutions {
+                let intervals = match self.camera.intervals(&format, resolution) {
+                    Ok(IntervalInfo::Discretes(intervals)) => intervals,
+                    Ok(IntervalInfo::Stepwise { min, max, .. }) => {
+                        let mut intervals = vec![min];
+                        intervals.extend(COMMON_FRAME_RATES.iter().map(|&fps| (1, fps)).filter(|&interval| {
+                            compare_intervals(min, interval).is_lt() && compare_intervals(max, interval).is_gt()
+                        }));
+                        intervals.push(max);
+                        intervals
+                    }
+                    Err(_) => Vec::new(),
+                };
+
+                modes.push(CaptureMode {
+                    format,
+                    resolution: (resolution.0 as usize, resolution.1 as usize),
+                    intervals,
+                });
+            }
+        }
+
+        Ok(modes)
+    }
+
+    /// Whether the camera can deliver `format` at the resolution and interval of `config`.
+    fn supports(&self, format: &[u8; 4], config: &CaptureConfig) -> bool {
+        let resolution = (config.resolution.0 as u32, config.resolution.1 as u32);
+
+        let resolution_ok = match self.camera.resolutions(format) {
+            Ok(ResolutionInfo::Discretes(sizes)) => sizes.contains(&resolution),
+            Ok(ResolutionInfo::Stepwise { min, max, step }) => fits_stepwise(resolution, min, max, step),
+            Err(_) => false,
+        };
+
+        resolution_ok
+            && match self.camera.intervals(format, resolution) {
+                Ok(IntervalInfo::Discretes(intervals)) => intervals
+                    .iter()
+                    .any(|&interval| compare_intervals(interval, config.interval).is_eq()),
+                Ok(IntervalInfo::Stepwise { min, max, .. }) => {
+                    compare_intervals(min, config.interval).is_le() && compare_intervals(max, config.interval).is_ge()
+                }
+                // Not every driver enumerates intervals, it will pick the closest one itself.
+                Err(_) => true,
+            }
+    }
+}
+
+impl FrameSource for CameraSource {
+    /// Starts the camera in the requested mode if it supports it. Otherwise the closest mode it
+    /// offers is used, keeping the requested pixel format if possible. Configuring it again
+    /// restarts it in the new mode.
+    fn configure(&mut self, config: &CaptureConfig) -> io::Result<CaptureConfig> {
+        let exact = match config.format {
+            Some(format) => self.supports(&format, config).then_some(format),
+            None => {
+                let offered = self.camera.formats().collect::<io::Result<Vec<_>>>()?;
+                PREFERRED_FORMATS
+                    .iter()
+                    .map(|format| *format.fourcc())
+                    .filter(|fourcc| offered.iter().any(|info| &info.format == fourcc))
+                    .find(|fourcc| self.supports(fourcc, config))
+            }
+        };
+
+        let config = match exact {
+            Some(format) => CaptureConfig {
+                format: Some(format),
+                ..config.clone()
+            },
+            None => closest_mode(&self.modes()?, config)
+                .ok_or_else(|| io::Error::new(ErrorKind::Unsupported, "camera offers no decodable mode"))?,
+        };
+
+        let format = config.format.expect("Chosen modes always have a format");
+        if PixelFormat::from_fourcc(&format).is_none() {
+            return Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "pixel format can not be decoded",
+            ));
+        }
+
+        self.close()?;
+        if self.stopped {
+            self.camera = Camera::new(&self.device)?;
+            self.stopped = false;
+            self.last_frame = None;
+        }
+
+        self.camera
+            .start(&Config {
+                interval: config.interval,
+                resolution: (config.resolution.0 as u32, config.resolution.1 as u32),
+                format: &format,
+                ..Default::default()
+            })
+            .map_err(to_io_error)?;
+
+        self.streaming = true;
+        self.interval = config.frame_duration();
+        Ok(config)
+    }
+
+    /// Opens the device node again and restores the controls set through this source before
+    /// configuring it.
+    fn reopen(&mut self, config: &CaptureConfig) -> io::Result<CaptureConfig> {
+        // The old device is most likely gone, it is released without stopping it properly.
+        let camera = Camera::new(&self.device)?;
+        self.streaming = false;
+        self.stopped = false;
+        self.camera = camera;
+        self.last_frame = None;
+
+        for &(id, value) in &self.applied_controls {
+            self.camera.set_control(id, &value)?;
+        }
+
+        self.configure(config)
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let frame = self.camera.capture()?;
+        let format = PixelFormat::from_fourcc(&frame.format)
+            .ok_or_else(|| io::Error::new(ErrorKind::Unsupported, "camera switched to an unknown pixel format"))?;
+
+        // A gap of more than half an interval past the expected one means a frame went missing.
+        let timestamp = Duration::from_micros(frame.get_timestamp());
+        let sequence = match self.last_frame {
+            Some((last, sequence)) => {
+                let intervals = timestamp.saturating_sub(last).as_secs_f64() / self.interval.as_secs_f64().max(1e-6);
+                sequence + (intervals.round() as u64).max(1)
+            }
+            None => 0,
+        };
+        self.last_frame = Some((timestamp, sequence));
+
+        Ok(Some(Frame {
+            format,
+            width: frame.resolution.0 as usize,
+            height: frame.resolution.1 as usize,
+            data: frame.to_vec(),
+            timestamp,
+            sequence,
+        }))
+    }
+
+    fn close(&mut self) -> io::Result<()> {
+        if self.streaming {
+            self.streaming = false;
+            self.stopped = true;
+            self.camera.stop()?;
+        }
+
+        Ok(())
+    }
+
+    fn is_live(&self) -> bool {
+        true
+    }
+}
+
+/// Frames stamped more than this long ago are taken to be stamped on a clock other than the
+/// monotonic one.
+const MAX_FRAME_AGE: Duration = Duration::from_secs(10);
+
+/// When a camera captured the frame it stamped with `timestamp`, as an [`Instant`]. V4L2 stamps
+/// buffers on the monotonic clock, frames stamped otherwise count as captured now.
+pub fn capture_instant(timestamp: Duration) -> Instant {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    // SAFETY: `now` is valid to write to and the monotonic clock always exists on Linux.
+    let monotonic = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } == 0;
+    let age = Duration::new(now.tv_sec as u64, now.tv_nsec as u32).checked_sub(timestamp);
+
+    match age {
+        Some(age) if monotonic && age < MAX_FRAME_AGE => Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+        _ => Instant::now(),
+    }
+}
+
+impl Drop for CameraSource {
+    fn drop(&mut self) {
+        // Failures leave the controls as they were set, there is nobody to report them to.
+        let _ = self.restore_controls();
+    }
+}
+
+/// One resolution a camera offers in one pixel format, along with the frame intervals it
+/// supports at that resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureMode {
+    pub format: [u8; 4],
+    pub resolution: (usize, usize),
+    /// Supported frame intervals, empty if the driver does not enumerate them.
+    pub intervals: Vec<(u32, u32)>,
+}
+
+/// Lists the V4L2 device nodes in `/dev`, in numeric order.
+pub fn list_devices() -> io::Result<Vec<PathBuf>> {
+    let mut devices: Vec<(u32, PathBuf)> = fs::read_dir("/dev")?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let number = path.file_name()?.to_str()?.strip_prefix("video")?.parse().ok()?;
+            Some((number, path))
+        })
+        .collect();
+
+    devices.sort();
+    Ok(devices.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Picks the decodable mode closest to `requested`: the nearest resolution first, then the
+/// nearest frame rate, then the most preferred pixel format. Modes in the requested pixel format
+/// are used if there are any.
+pub fn closest_mode(modes: &[CaptureMode], requested: &CaptureConfig) -> Option<CaptureConfig> {
+    let decodable = modes
+        .iter()
+        .filter_map(|mode| Some((mode, rank(mode.format)?)))
+        .collect::<Vec<_>>();
+    let matching = decodable
+        .iter()
+        .filter(|(mode, _)| requested.format == Some(mode.format))
+        .copied()
+        .collect::<Vec<_>>();
+    let candidates = if matching.is_empty() { decodable } else { matching };
+
+    let target = requested.interval.1 as f64 / requested.interval.0.max(1) as f64;
+    let fps_error = |interval: (u32, u32)| (interval.1 as f64 / interval.0.max(1) as f64 - target).abs();
+    let resolution_distance = |mode: &CaptureMode| {
+        mode.resolution.0.abs_diff(requested.resolution.0) + mode.resolution.1.abs_diff(requested.resolution.1)
+    };
+
+    candidates
+        .into_iter()
+        .map(|(mode, rank)| {
+            // Modes that do not enumerate intervals are assumed to hit the requested one.
+            let interval = mode
+                .intervals
+                .iter()
+                .copied()
+                .min_by(|&a, &b| fps_error(a).total_cmp(&fps_error(b)))
+                .unwrap_or(requested.interval);
+            (mode, interval, rank)
+        })
+        .min_by(|a, b| {
+            resolution_distance(a.0)
+                .cmp(&resolution_distance(b.0))
+                .then(fps_error(a.1).total_cmp(&fps_error(b.1)))
+                .then(a.2.cmp(&b.2))
+        })
+        .map(|(mode, interval, _)| CaptureConfig {
+            resolution: mode.resolution,
+            interval,
+            format: Some(mode.format),
+        })
+}
+
+/// Position of `format` in [`PREFERRED_FORMATS`], `None` if it can not be decoded.
+fn rank(format: [u8; 4]) -> Option<usize> {
+    PREFERRED_FORMATS
+        .iter()
+        .position(|preferred| preferred.fourcc() == &format)
+}
+
+fn fits_stepwise(size: (u32, u32), min: (u32, u32), max: (u32, u32), step: (u32, u32)) -> bool {
+    let fits = |value: u32, min: u32, max: u32, step: u32| {
+        (min..=max).contains(&value) && (value - min).is_multiple_of(step.max(1))
+    };
+
+    fits(size.0, min.0, max.0, step.0) && fits(size.1, min.1, max.1, step.1)
+}
+
+/// Compares two `(numerator, denominator)` frame intervals by the time they describe.
+fn compare_intervals(a: (u32, u32), b: (u32, u32)) -> Ordering {
+    (a.0 as u64 * b.1 as u64).cmp(&(b.0 as u64 * a.1 as u64))
+}
+
+fn to_io_error(err: rscam::Error) -> io::Error {
+    match err {
+        rscam::Error::Io(err) => err,
+        other => io::Error::new(ErrorKind::InvalidInput, other.to_string()),
+    }
+}
@@ -0,0 +1,253 @@
+use std::str::FromStr;
+
+use rscam::{Control, CtrlData};
+
+/// A V4L2 control of a camera, like its brightness or exposure time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CameraControl {
+    pub id: u32,
+    /// Name as reported by the driver, e.g. `White Balance Temperature`.
+    pub name: String,
+    pub kind: ControlKind,
+    /// Current value, `None` for controls without one like buttons.
+    pub value: Option<i64>,
+    pub default: Option<i64>,
+    /// The control can not be changed, at least while the controls it depends on are set as
+    /// they are.
+    pub read_only: bool,
+    pub inactive: bool,
+}
+
+/// Values a [`CameraControl`] takes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlKind {
+    Integer {
+        minimum: i64,
+        maximum: i64,
+        step: i64,
+    },
+    Boolean,
+    /// Indices and names of the menu items.
+    Menu(Vec<(i64, String)>),
+    /// Buttons, strings, bitmasks and types rscam does not know, which can be listed but not set.
+    Other,
+}
+
+impl CameraControl {
+    /// Converts a control as read by rscam, `None` for the headings of control classes.
+    pub(super) fn from_rscam(control: Control) -> Option<Self> {
+        let (kind, value, default) = match control.data {
+            CtrlData::Integer {
+                value,
+                default,
+                minimum,
+                maximum,
+                step,
+            } => (
+                ControlKind::Integer {
+                    minimum: minimum.into(),
+                    maximum: maximum.into(),
+                    step: step.into(),
+                },
+                Some(value.into()),
+                Some(default.into()),
+            ),
+            CtrlData::Integer64 {
+                value,
+                default,
+                minimum,
+                maximum,
+                step,
+            } => (
+                ControlKind::Integer { minimum, maximum, step },
+                Some(value),
+                Some(default),
+            ),
+            CtrlData::Boolean { value, default } => (ControlKind::Boolean, Some(value.into()), Some(default.into())),
+            CtrlData::Menu { value, default, items } => (
+                ControlKind::Menu(items.into_iter().map(|item| (item.index.into(), item.name)).collect()),
+                Some(value.into()),
+                Some(default.into()),
+            ),
+            CtrlData::IntegerMenu { value, default, items } => (
+                ControlKind::Menu(
+                    items
+                        .into_iter()
+                        .map(|item| (item.index.into(), item.value.to_string()))
+                        .collect(),
+                ),
+                Some(value.into()),
+                Some(default.into()),
+            ),
+            CtrlData::Bitmask { value, default, .. } => (ControlKind::Other, Some(value.into()), Some(default.into())),
+            CtrlData::Button | CtrlData::String { .. } | CtrlData::Unknown => (ControlKind::Other, None, None),
+            CtrlData::CtrlClass => return None,
+        };
+
+        Some(Self {
+            id: control.id,
+            name: control.name,
+            kind,
+            value,
+            default,
+            read_only: control.flags & rscam::FLAG_READ_ONLY != 0,
+            inactive: control.flags & rscam::FLAG_INACTIVE != 0,
+        })
+    }
+
+    /// The name in lower case with words joined by underscores, e.g. `white_balance_temperature`,
+    /// like `v4l2-ctl` names controls.
+    pub fn key(&self) -> String {
+        let mut key = String::new();
+        for word in self
+            .name
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            if !key.is_empty() {
+                key.push('_');
+            }
+            key.push_str(&word.to_ascii_lowercase());
+        }
+        key
+    }
+
+    /// Whether `name` names this control, either by its [`key`](Self::key) or by the name the
+    /// driver reports.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.key() == name.to_ascii_lowercase() || self.name.eq_ignore_ascii_case(name)
+    }
+
+    /// Formats `value` as a number, along with the name of the menu item it stands for.
+    pub fn format_value(&self, value: i64) -> String {
+        match &self.kind {
+            ControlKind::Menu(items) => match items.iter().find(|(index, _)| *index == value) {
+                Some((_, name)) => format!("{} ({})", value, name),
+                None => value.to_string(),
+            },
+            _ => value.to_string(),
+        }
+    }
+
+    /// Parses a value for this control: a number, `true` or `false` for booleans, or the name of
+    /// a menu item.
+    pub fn parse_value(&self, s: &str) -> Result<i64, String> {
+        let value = match &self.kind {
+            ControlKind::Boolean if s.eq_ignore_ascii_case("true") => Some(1),
+            ControlKind::Boolean if s.eq_ignore_ascii_case("false") => Some(0),
+            ControlKind::Menu(items) => items
+                .iter()
+                .find(|(_, name)| name.eq_ignore_ascii_case(s))
+                .map(|(index, _)| *index),
+            ControlKind::Other => return Err(format!("control {:?} can not be set", self.key())),
+            _ => None,
+        };
+
+        let value = match value {
+            Some(value) => value,
+            None => s
+                .parse::<i64>()
+                .map_err(|_| format!("invalid value {:?} for control {:?}", s, self.key()))?,
+        };
+
+        let valid = match &self.kind {
+            ControlKind::Integer { minimum, maximum, .. } => (*minimum..=*maximum).contains(&value),
+            ControlKind::Boolean => value == 0 || value == 1,
+            ControlKind::Menu(items) => items.iter().any(|(index, _)| *index == value),
+            ControlKind::Other => false,
+        };
+
+        if valid {
+            Ok(value)
+        } else {
+            Err(format!("value {} is out of range for control {:?}", value, self.key()))
+        }
+    }
+}
+
+/// An automatic camera setting that can be locked, so that the camera stops adjusting it while
+/// motion is extracted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoControl {
+    Exposure,
+    WhiteBalance,
+    Focus,
+    Gain,
+}
+
+impl AutoControl {
+    /// The control that switches the setting between automatic and manual, and its manual value.
+    pub fn manual_setting(self) -> (u32, i64) {
+        match self {
+            AutoControl::Exposure => (rscam::CID_EXPOSURE_AUTO, rscam::EXPOSURE_MANUAL.into()),
+            AutoControl::WhiteBalance => (rscam::CID_AUTO_WHITE_BALANCE, 0),
+            AutoControl::Focus => (rscam::CID_FOCUS_AUTO, 0),
+            AutoControl::Gain => (rscam::CID_AUTOGAIN, 0),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AutoControl::Exposure => "exposure",
+            AutoControl::WhiteBalance => "white-balance",
+            AutoControl::Focus => "focus",
+            AutoControl::Gain => "gain",
+        }
+    }
+}
+
+impl FromStr for AutoControl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "exposure" => Ok(AutoControl::Exposure),
+            "white-balance" | "wb" => Ok(AutoControl::WhiteBalance),
+            "focus" => Ok(AutoControl::Focus),
+            "gain" => Ok(AutoControl::Gain),
+            _ => Err(format!(
+                "unknown setting {:?}, expected exposure, white-balance, focus or gain",
+                s
+            )),
+        }
+    }
+}
+
+/// Frequency of the mains power, which the camera can filter the flicker of lights out at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerLine {
+    Off,
+    Hz50,
+    Hz60,
+    Auto,
+}
+
+impl PowerLine {
+    /// Value of the power line frequency control.
+    pub fn value(self) -> i64 {
+        let value = match self {
+            PowerLine::Off => rscam::CID_POWER_LINE_FREQUENCY_DISABLED,
+            PowerLine::Hz50 => rscam::CID_POWER_LINE_FREQUENCY_50HZ,
+            PowerLine::Hz60 => rscam::CID_POWER_LINE_FREQUENCY_60HZ,
+            PowerLine::Auto => rscam::CID_POWER_LINE_FREQUENCY_AUTO,
+        };
+        value.into()
+    }
+}
+
+impl FromStr for PowerLine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().trim_end_matches("hz") {
+            "off" => Ok(PowerLine::Off),
+            "50" => Ok(PowerLine::Hz50),
+            "60" => Ok(PowerLine::Hz60),
+            "auto" => Ok(PowerLine::Auto),
+            _ => Err(format!(
+                "unknown power line frequency {:?}, expected off, 50, 60 or auto",
+                s
+            )),
+        }
+    }
+}
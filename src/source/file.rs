@@ -0,0 +1,158 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::{CaptureConfig, FrameSource};
+use crate::avi::AviReader;
+use crate::decode;
+use crate::frame::{Frame, PixelFormat};
+
+/// Recorded footage that can stand in for a live camera.
+pub enum FileInput {
+    /// A Motion-JPEG AVI file.
+    Avi(AviReader<BufReader<File>>),
+    /// A directory of JPEG and PNG images, played back in file name order.
+    Sequence(ImageSequence),
+}
+
+impl FileInput {
+    /// Opens `path` as an image sequence if it is a directory and as an AVI file otherwise.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            ImageSequence::open(path).map(FileInput::Sequence)
+        } else {
+            AviReader::open(path).map(FileInput::Avi)
+        }
+    }
+
+    pub fn resolution(&self) -> (usize, usize) {
+        match self {
+            FileInput::Avi(avi) => (avi.width(), avi.height()),
+            FileInput::Sequence(seq) => (seq.width, seq.height),
+        }
+    }
+}
+
+impl FrameSource for FileInput {
+    /// Keeps the requested frame interval unless the file declares its own.
+    fn configure(&mut self, config: &CaptureConfig) -> io::Result<CaptureConfig> {
+        let (interval, format) = match self {
+            FileInput::Avi(avi) if !avi.frame_interval().is_zero() => {
+                let micros = avi.frame_interval().as_micros().min(u32::MAX as u128) as u32;
+                ((micros, 1_000_000), PixelFormat::Mjpeg)
+            }
+            FileInput::Avi(avi) => {
+                avi.set_frame_interval(config.frame_duration());
+                (config.interval, PixelFormat::Mjpeg)
+            }
+            FileInput::Sequence(seq) => {
+                seq.interval = config.frame_duration();
+                (config.interval, seq.format)
+            }
+        };
+
+        Ok(CaptureConfig {
+            resolution: self.resolution(),
+            interval,
+            format: Some(*format.fourcc()),
+        })
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let (width, height) = self.resolution();
+        match self {
+            FileInput::Avi(avi) => Ok(avi.next_frame()?.map(|data| Frame {
+                format: PixelFormat::Mjpeg,
+                width,
+                height,
+                data,
+                timestamp: avi.frame_interval() * avi.sequence() as u32,
+                sequence: avi.sequence(),
+            })),
+            FileInput::Sequence(seq) => seq.next_frame(),
+        }
+    }
+
+    fn is_live(&self) -> bool {
+        false
+    }
+}
+
+pub struct ImageSequence {
+    paths: Vec<PathBuf>,
+    next: usize,
+    /// Format of the first image, which the sequence is described by.
+    format: PixelFormat,
+    width: usize,
+    height: usize,
+    /// Time between two images, which they are stamped with.
+    interval: Duration,
+}
+
+impl ImageSequence {
+    /// Collects every `.jpg`, `.jpeg` and `.png` file in `dir`. The resolution of the sequence is
+    /// taken from its first image.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() && image_format(&path).is_some() {
+                paths.push(path);
+            }
+        }
+
+        paths.sort();
+
+        let first = paths
+            .first()
+            .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "directory contains no JPEG or PNG images"))?;
+        let format = image_format(first).expect("Only images are collected");
+        let (width, height) =
+            decode::probe(format, &fs::read(first)?).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+
+        Ok(Self {
+            paths,
+            next: 0,
+            format,
+            width,
+            height,
+            interval: Duration::ZERO,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let Some(path) = self.paths.get(self.next) else {
+            return Ok(None);
+        };
+
+        let sequence = self.next as u64;
+        self.next += 1;
+        Ok(Some(Frame {
+            format: image_format(path).expect("Only images are collected"),
+            width: self.width,
+            height: self.height,
+            data: fs::read(path)?,
+            timestamp: self.interval * sequence as u32,
+            sequence,
+        }))
+    }
+}
+
+fn image_format(path: &Path) -> Option<PixelFormat> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => Some(PixelFormat::Mjpeg),
+        "png" => Some(PixelFormat::Png),
+        _ => None,
+    }
+}
@@ -0,0 +1,166 @@
+use std::f32::consts::TAU;
+use std::io;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::{CaptureConfig, FrameSource};
+use crate::encode;
+use crate::frame::{Frame, PixelFormat};
+
+const BACKGROUND: u32 = 0x202020;
+
+/// Square colour and velocity in pixels per frame.
+const SQUARES: [(u32, i64, i64); 3] = [(0xFF0000, 3, 1), (0x00FF00, -2, 2), (0x0000FF, 1, -3)];
+
+/// Frames per full turn of the rotating bar.
+const BAR_PERIOD: u32 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Red, green and blue squares drifting across a grey background, wrapping at the edges.
+    Squares,
+    /// A white bar rotating around the centre of a black frame.
+    Bar,
+    /// Grey noise, different for every frame but reproducible from the seed.
+    Noise,
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "squares" => Ok(Pattern::Squares),
+            "bar" => Ok(Pattern::Bar),
+            "noise" => Ok(Pattern::Noise),
+            _ => Err(format!("unknown pattern {:?}, expected squares, bar or noise", s)),
+        }
+    }
+}
+
+/// Generates a test pattern instead of capturing one, so the pipeline can run without a camera
+/// and produce the same frames on every run.
+pub struct SyntheticSource {
+    pattern: Pattern,
+    seed: u64,
+    width: usize,
+    height: usize,
+    /// Time between two frames, which the frames are stamped with.
+    interval: Duration,
+    index: u64,
+    pixels: Vec<u32>,
+}
+
+impl SyntheticSource {
+    pub fn open(pattern: Pattern, seed: u64) -> Self {
+        Self {
+            pattern,
+            seed,
+            width: 0,
+            height: 0,
+            interval: Duration::ZERO,
+            index: 0,
+            pixels: Vec::new(),
+        }
+    }
+
+    /// Renders frame number `index` of the pattern as `0RGB` pixels.
+    pub fn render(&mut self, index: u64) -> &[u32] {
+        self.pixels.resize(self.width * self.height, 0);
+
+        match self.pattern {
+            Pattern::Squares => self.render_squares(index),
+            Pattern::Bar => self.render_bar(index),
+            Pattern::Noise => self.render_noise(index),
+        }
+
+        &self.pixels
+    }
+
+    fn render_squares(&mut self, index: u64) {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let size = (width.min(height) / 8).max(1);
+        let index = index as i64;
+
+        self.pixels.fill(BACKGROUND);
+        for (i, &(color, vx, vy)) in SQUARES.iter().enumerate() {
+            let i = i as i64;
+            let x0 = i * width / 3 + vx * index;
+            let y0 = (i + 1) * height / 4 + vy * index;
+
+            for dy in 0..size {
+                let y = (y0 + dy).rem_euclid(height) as usize;
+                for dx in 0..size {
+                    let x = (x0 + dx).rem_euclid(width) as usize;
+                    self.pixels[y * self.width + x] = color;
+                }
+            }
+        }
+    }
+
+    fn render_bar(&mut self, index: u64) {
+        let (sin, cos) = ((index % BAR_PERIOD as u64) as f32 * TAU / BAR_PERIOD as f32).sin_cos();
+        let (cx, cy) = (self.width as f32 / 2.0, self.height as f32 / 2.0);
+        let radius = self.width.min(self.height) as f32 * 0.4;
+        let thickness = (self.height as f32 / 40.0).max(1.0);
+
+        for (i, pixel) in self.pixels.iter_mut().enumerate() {
+            let dx = (i % self.width) as f32 + 0.5 - cx;
+            let dy = (i / self.width) as f32 + 0.5 - cy;
+
+            let along = dx * cos + dy * sin;
+            let across = dx * sin - dy * cos;
+            *pixel = if along.abs() <= radius && across.abs() <= thickness {
+                0xFFFFFF
+            } else {
+                0
+            };
+        }
+    }
+
+    fn render_noise(&mut self, index: u64) {
+        let mut state = (self.seed ^ (index + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1;
+
+        for pixel in &mut self.pixels {
+            // xorshift64*
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+
+            let value = (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u32;
+            *pixel = (value << 16) | (value << 8) | value;
+        }
+    }
+}
+
+impl FrameSource for SyntheticSource {
+    /// Generates frames at the requested resolution, always as packed RGB.
+    fn configure(&mut self, config: &CaptureConfig) -> io::Result<CaptureConfig> {
+        (self.width, self.height) = config.resolution;
+        self.interval = config.frame_duration();
+
+        Ok(CaptureConfig {
+            format: Some(*PixelFormat::Rgb24.fourcc()),
+            ..config.clone()
+        })
+    }
+
+    fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let index = self.index;
+        self.index += 1;
+
+        let data = encode::to_rgb(self.render(index));
+        Ok(Some(Frame {
+            format: PixelFormat::Rgb24,
+            width: self.width,
+            height: self.height,
+            data,
+            timestamp: self.interval * index as u32,
+            sequence: index,
+        }))
+    }
+
+    fn is_live(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+use std::time::Duration;
+
+/// A step every frame goes through on its way from the camera to the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Waiting for the source to deliver the frame.
+    Capture,
+    /// Decoding, mirroring and prefiltering.
+    Decode,
+    /// Extracting the motion, detecting objects and scoring events.
+    Diff,
+    /// Drawing the overlays and handing the picture to the window.
+    Present,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::Capture, Stage::Decode, Stage::Diff, Stage::Present];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Capture => "capture",
+            Stage::Decode => "decode",
+            Stage::Diff => "diff",
+            Stage::Present => "present",
+        }
+    }
+}
+
+/// A queue frames wait in between two threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Queue {
+    /// Captured frames waiting to be decoded.
+    Captured,
+    /// Decoded frames waiting for the main loop.
+    Decoded,
+}
+
+impl Queue {
+    pub fn name(self) -> &'static str {
+        match self {
+            Queue::Captured => "captured",
+            Queue::Decoded => "decoded",
+        }
+    }
+}
+
+/// Mean and maximum of a series of values.
+#[derive(Debug, Clone, Copy, Default)]
+struct Series {
+    total: f64,
+    max: f64,
+    count: u64,
+}
+
+impl Series {
+    fn add(&mut self, value: f64) {
+        self.total += value;
+        self.max = self.max.max(value);
+        self.count += 1;
+    }
+
+    fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total / self.count as f64)
+    }
+}
+
+/// Timings, queue depths, dropped frames and latency of the pipeline, collected over a reporting
+/// period.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineStats {
+    /// Seconds spent in each [`Stage`].
+    stages: [Series; 4],
+    /// Frames left in each [`Queue`] when one was taken out.
+    queues: [Series; 2],
+    /// Seconds from capture until the frame was shown.
+    latency: Series,
+    frames: u64,
+    dropped: u64,
+    /// Kept across periods, so that gaps between them are counted too.
+    last_sequence: Option<u64>,
+}
+
+impl PipelineStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a frame by its sequence number, along with the frames missing before it. Numbers
+    /// that go back, like those of a camera that was reconnected, start counting over.
+    pub fn record_frame(&mut self, sequence: u64) {
+        if let Some(last) = self.last_sequence
+            && sequence > last
+        {
+            self.dropped += sequence - last - 1;
+        }
+
+        self.last_sequence = Some(sequence);
+        self.frames += 1;
+    }
+
+    pub fn record_stage(&mut self, stage: Stage, duration: Duration) {
+        self.stages[stage as usize].add(duration.as_secs_f64());
+    }
+
+    /// Records that `depth` frames were left in `queue` after one was taken out.
+    pub fn record_queue(&mut self, queue: Queue, depth: usize) {
+        self.queues[queue as usize].add(depth as f64);
+    }
+
+    pub fn record_latency(&mut self, latency: Duration) {
+        self.latency.add(latency.as_secs_f64());
+    }
+
+    /// Starts a new period.
+    pub fn reset(&mut self) {
+        *self = Self {
+            last_sequence: self.last_sequence,
+            ..Self::default()
+        };
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Frames missing from the sequence numbers, dropped by the driver because the pipeline did
+    /// not take them in time, or lost on the way from the camera.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Mean and longest time spent in `stage`, if any frame went through it.
+    pub fn stage(&self, stage: Stage) -> Option<(Duration, Duration)> {
+        let series = &self.stages[stage as usize];
+        series
+            .mean()
+            .map(|mean| (Duration::from_secs_f64(mean), Duration::from_secs_f64(series.max)))
+    }
+
+    /// Mean and largest number of frames waiting in `queue`.
+    pub fn queue(&self, queue: Queue) -> Option<(f64, usize)> {
+        let series = &self.queues[queue as usize];
+        series.mean().map(|mean| (mean, series.max as usize))
+    }
+
+    /// Mean and longest time from capture until the frame was shown.
+    pub fn latency(&self) -> Option<(Duration, Duration)> {
+        self.latency
+            .mean()
+            .map(|mean| (Duration::from_secs_f64(mean), Duration::from_secs_f64(self.latency.max)))
+    }
+
+    /// Describes the period in a few short lines for the on-screen display.
+    pub fn hud_lines(&self) -> Vec<String> {
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        let stages = Stage::ALL
+            .iter()
+            .filter_map(|&stage| {
+                let (mean, _) = self.stage(stage)?;
+                Some(format!("{} {:.1}", stage.name(), millis(mean)))
+            })
+            .collect::<Vec<_>>();
+
+        let queues = [Queue::Captured, Queue::Decoded]
+            .iter()
+            .map(|&queue| match self.queue(queue) {
+                Some((mean, max)) => format!("{:.1}/{}", mean, max),
+                None => "-".to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        let latency = match self.latency() {
+            Some((mean, _)) => format!("{:.0} MS", millis(mean)),
+            None => "-".to_string(),
+        };
+
+        vec![
+            format!("{} MS", stages.join(" ")).to_ascii_uppercase(),
+            format!("QUEUED {} DROPPED {}", queues.join(" "), self.dropped),
+            format!("LATENCY {}", latency),
+        ]
+    }
+}
+
+/// One line summing up the period, for printing to stderr.
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+        write!(f, "{} frames, {} dropped", self.frames, self.dropped)?;
+
+        if let Some((mean, max)) = self.latency() {
+            write!(f, ", latency {:.1} ms (max {:.1})", millis(mean), millis(max))?;
+        }
+
+        for stage in Stage::ALL {
+            if let Some((mean, max)) = self.stage(stage) {
+                write!(f, ", {} {:.1} ms (max {:.1})", stage.name(), millis(mean), millis(max))?;
+            }
+        }
+
+        for queue in [Queue::Captured, Queue::Decoded] {
+            if let Some((mean, max)) = self.queue(queue) {
+                write!(f, ", {} queue {:.1} (max {})", queue.name(), mean, max)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,613 @@
+use std::io::ErrorKind;
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError, SendError, SyncSender, sync_channel};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use motion_extraction::{
+    CaptureConfig, ChannelDelays, ClipRecorder, EventDetector, Frame, FrameClock, FrameSource, MotionDetector,
+    MotionEvent, MotionExtractor, PipelineStats, PixelFormat, Prefilter, Queue, SpatialFilter, Stage, capture_instant,
+    encode_jpeg, motion_score,
+};
+
+use crate::cli::Args;
+use crate::controls::Controls;
+use crate::hud::{self, FpsCounter};
+use crate::list;
+use crate::roi_editor::RoiEditor;
+use crate::signal::SignalMonitor;
+
+/// Outline colour of moving objects.
+const BLOB_COLOUR: u32 = 0x00FF00;
+
+/// Colour of an area to ignore while it is being drawn.
+const ROI_COLOUR: u32 = 0xFF00FF;
+
+/// Wait before the first attempt to reopen a lost camera, doubled after every failed attempt up
+/// to the maximum.
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(8);
+
+/// Sending half of a [`counted_channel`].
+pub struct CountedSender<T> {
+    tx: SyncSender<T>,
+    depth: Arc<AtomicUsize>,
+}
+
+impl<T> Clone for CountedSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            depth: Arc::clone(&self.depth),
+        }
+    }
+}
+
+impl<T> CountedSender<T> {
+    /// Sends `value`, blocking while the channel is full. A sender that is blocked counts as
+    /// waiting in the channel already.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        self.tx.send(value).inspect_err(|_| {
+            self.depth.fetch_sub(1, Ordering::Relaxed);
+        })
+    }
+}
+
+/// Receiving half of a [`counted_channel`], which hands out every value along with the number of
+/// values still waiting behind it.
+pub struct CountedReceiver<T> {
+    rx: Receiver<T>,
+    depth: Arc<AtomicUsize>,
+}
+
+impl<T> CountedReceiver<T> {
+    pub fn recv(&self) -> Result<(T, usize), RecvError> {
+        let value = self.rx.recv()?;
+        Ok((value, self.depth.fetch_sub(1, Ordering::Relaxed) - 1))
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<(T, usize), RecvTimeoutError> {
+        let value = self.rx.recv_timeout(timeout)?;
+        Ok((value, self.depth.fetch_sub(1, Ordering::Relaxed) - 1))
+    }
+}
+
+/// A [`sync_channel`] that keeps count of the values waiting in it, to tell whether the threads
+/// it connects keep up with each other.
+pub fn counted_channel<T>(bound: usize) -> (CountedSender<T>, CountedReceiver<T>) {
+    let (tx, rx) = sync_channel(bound);
+    let depth = Arc::new(AtomicUsize::new(0));
+    (
+        CountedSender {
+            tx,
+            depth: Arc::clone(&depth),
+        },
+        CountedReceiver { rx, depth },
+    )
+}
+
+/// A frame as it comes out of the capture thread.
+struct Captured {
+    frame: Frame,
+    /// When the frame was captured, on the clock [`Instant`]s use.
+    captured: Instant,
+    /// How long the source took to deliver the frame.
+    wait: Duration,
+}
+
+/// Pulls frames out of `source`, sleeping between frames if an `interval` is given. Live sources
+/// that fail are reopened in `config` if `reconnect` is set, clearing `signal` meanwhile.
+fn capture_thread(
+    mut source: Box<dyn FrameSource>,
+    config: CaptureConfig,
+    interval: Option<Duration>,
+    reconnect: bool,
+    signal: Arc<AtomicBool>,
+    tx_capture: CountedSender<Captured>,
+    rx_close: Receiver<()>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let start = Instant::now();
+        let mut frames = 0;
+
+        while rx_close.try_recv().is_err() {
+            let waiting = Instant::now();
+            let frame = match source.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(err) if reconnect && source.is_live() => {
+                    eprintln!("Error capturing frame: {}, reconnecting", err);
+                    signal.store(false, Ordering::Relaxed);
+
+                    if !reopen(source.as_mut(), &config, &rx_close) {
+                        break;
+                    }
+
+                    signal.store(true, Ordering::Relaxed);
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!("Error capturing frame: {}", err);
+                    break;
+                }
+            };
+
+            let wait = waiting.elapsed();
+
+            if let Some(interval) = interval {
+                thread::sleep((start + interval * frames).saturating_duration_since(Instant::now()));
+                frames += 1;
+            }
+
+            // Cameras stamp frames when they were taken, footage counts as taken once it is due.
+            let captured = if source.is_live() {
+                capture_instant(frame.timestamp)
+            } else {
+                Instant::now()
+            };
+
+            if tx_capture.send(Captured { frame, captured, wait }).is_err() {
+                break;
+            }
+        }
+
+        if let Err(err) = source.close() {
+            eprintln!("Error closing source: {}", err);
+        }
+    })
+}
+
+/// Tries to reopen `source` in `config`, waiting longer after every failed attempt. Returns
+/// whether it succeeded before being asked to close.
+fn reopen(source: &mut dyn FrameSource, config: &CaptureConfig, rx_close: &Receiver<()>) -> bool {
+    let lost = Instant::now();
+    let mut delay = RECONNECT_DELAY;
+
+    for attempt in 1.. {
+        if !matches!(rx_close.recv_timeout(delay), Err(RecvTimeoutError::Timeout)) {
+            return false;
+        }
+
+        match source.reopen(config) {
+            Ok(reopened) if reopened == *config => {
+                eprintln!(
+                    "Reconnected after {:.1} s and {} attempts",
+                    lost.elapsed().as_secs_f64(),
+                    attempt
+                );
+                return true;
+            }
+            Ok(_) => eprintln!("Error reconnecting: the source came back in a different mode"),
+            Err(err) if err.kind() == ErrorKind::Unsupported => {
+                eprintln!("Error reconnecting: {}", err);
+                return false;
+            }
+            Err(err) => eprintln!("Error reconnecting: {}", err),
+        }
+
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+
+    unreachable!("Attempts are unbounded")
+}
+
+/// A decoded frame of the `camera` with that index, along with the JPEG data it was decoded from
+/// if that was kept.
+pub struct Decoded {
+    pub camera: usize,
+    /// When the frame was captured, see [`Frame::timestamp`].
+    timestamp: Duration,
+    sequence: u64,
+    /// When the frame was captured, on the clock [`Instant`]s use.
+    pub captured: Instant,
+    /// Time spent waiting for the frame and decoding it.
+    capture_wait: Duration,
+    decode_time: Duration,
+    /// Frames still waiting to be decoded when this one was taken up.
+    queued: usize,
+    pixels: Vec<u32>,
+    jpeg: Option<Vec<u8>>,
+}
+
+/// Decodes frames of `camera` into `0RGB` pixels and applies `prefilter` to them. With a
+/// `clip_quality`, every frame is passed on as JPEG data as well, at the capture resolution and
+/// before mirroring and filtering, which reuses the data of Motion-JPEG frames instead of encoding
+/// them again.
+///
+/// Stops once the capture thread does, or once nobody receives the decoded frames any more.
+fn decode_thread(
+    camera: usize,
+    (width, height): (usize, usize),
+    mirror: Arc<AtomicBool>,
+    prefilter: Prefilter,
+    clip_quality: Option<u8>,
+    rx_capture: CountedReceiver<Captured>,
+    tx_decode: CountedSender<Decoded>,
+) -> thread::JoinHandle<()> {
+    let mut decode_buf = vec![0u32; width * height];
+    let mut filter = SpatialFilter::new(prefilter, (width, height));
+    let (out_width, out_height) = filter.output_size();
+
+    thread::spawn(move || {
+        while let Ok((Captured { frame, captured, wait }, queued)) = rx_capture.recv() {
+            let decoding = Instant::now();
+
+            if let Err(err) = motion_extraction::decode(&frame, width, height, &mut decode_buf) {
+                eprintln!("Error decoding frame: {}", err);
+                continue;
+            }
+
+            // Clips are stored the way the camera captured them, so they are encoded before
+            // mirroring. Frames that fail to encode are left out of the clip.
+            let jpeg = clip_quality.and_then(|quality| {
+                if frame.format == PixelFormat::Mjpeg {
+                    return Some(frame.data);
+                }
+
+                let mut jpeg = Vec::new();
+                match encode_jpeg(&decode_buf, width, height, quality, &mut jpeg) {
+                    Ok(()) => Some(jpeg),
+                    Err(err) => {
+                        eprintln!("Error encoding clip frame: {}", err);
+                        None
+                    }
+                }
+            });
+
+            if mirror.load(Ordering::Relaxed) {
+                motion_extraction::mirror(&mut decode_buf, width);
+            }
+
+            let pixels = if prefilter == Prefilter::Off {
+                mem::replace(&mut decode_buf, vec![0u32; width * height])
+            } else {
+                let mut pixels = vec![0u32; out_width * out_height];
+                filter.apply(&decode_buf, &mut pixels);
+                pixels
+            };
+
+            let decoded = Decoded {
+                camera,
+                timestamp: frame.timestamp,
+                sequence: frame.sequence,
+                captured,
+                capture_wait: wait,
+                decode_time: decoding.elapsed(),
+                queued,
+                pixels,
+                jpeg,
+            };
+            if tx_decode.send(decoded).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Number of frames of `interval` in `seconds`, at least one.
+pub fn frames_in(seconds: f64, interval: Duration) -> usize {
+    (seconds / interval.as_secs_f64().max(1e-6)).ceil().max(1.0) as usize
+}
+
+/// One camera, or other source, with its own capture and decode threads and its own motion
+/// history, events and clips.
+pub struct Tile {
+    /// The device or file the frames come from.
+    pub name: String,
+    pub config: CaptureConfig,
+    /// Size of the frames after prefiltering, which everything after decoding works on.
+    pub width: usize,
+    pub height: usize,
+    pub interval: Duration,
+    pub extractor: MotionExtractor,
+    pub roi_editor: RoiEditor,
+    pub monitor: SignalMonitor,
+    fps: FpsCounter,
+    frame_rate: f64,
+    detector: MotionDetector,
+    magnitude: Vec<u8>,
+    /// The motion of the latest frame, without overlays.
+    pub display_buf: Vec<u32>,
+    overlay_buf: Vec<u32>,
+    sensor: Option<EventDetector>,
+    clock: FrameClock,
+    score: f64,
+    clips: Option<ClipRecorder>,
+    /// Statistics of the current reporting period and of the last complete one.
+    pub stats: PipelineStats,
+    report: Option<PipelineStats>,
+    tx_close: SyncSender<()>,
+    cap_handle: thread::JoinHandle<()>,
+    dec_handle: thread::JoinHandle<()>,
+}
+
+impl Tile {
+    /// Configures `source` and starts capturing from it as the `camera` with that index, sending
+    /// the decoded frames to `tx_decode`.
+    pub fn start(
+        camera: usize,
+        name: String,
+        mut source: Box<dyn FrameSource>,
+        args: &Args,
+        controls: &Controls,
+        tx_decode: CountedSender<Decoded>,
+    ) -> Self {
+        let requested = args.capture_config();
+        let config = source.configure(&requested).expect("Failed to start capture");
+        if source.is_live() && (config.resolution != requested.resolution || config.interval != requested.interval) {
+            eprintln!(
+                "Requested mode is not supported by {}, using {}x{} at {} fps",
+                name,
+                config.resolution.0,
+                config.resolution.1,
+                list::frame_rate(config.interval)
+            );
+        }
+
+        let (width, height) = args.prefilter.output_size(config.resolution);
+        let interval = config.frame_duration();
+        let pacing = (!source.is_live() && !args.no_pacing).then_some(interval);
+        let clock = if source.is_live() {
+            FrameClock::Live
+        } else {
+            FrameClock::footage(SystemTime::now())
+        };
+
+        let (tx_cap, rx_cap) = counted_channel(4);
+        let (tx_close, rx_close) = sync_channel(1);
+
+        let monitor = SignalMonitor::new();
+        let cap_handle = capture_thread(
+            source,
+            config.clone(),
+            pacing,
+            !args.no_reconnect,
+            Arc::clone(&monitor.signal),
+            tx_cap,
+            rx_close,
+        );
+        let dec_handle = decode_thread(
+            camera,
+            config.resolution,
+            Arc::clone(&controls.mirror),
+            args.prefilter,
+            args.clips.is_some().then_some(args.jpeg_quality),
+            rx_cap,
+            tx_decode,
+        );
+
+        let effect = &controls.effects[camera];
+        // The delays are only set once their unit is, so that delays in milliseconds never size
+        // the history as if they counted frames.
+        let mut extractor = MotionExtractor::new(width, height, ChannelDelays::default());
+        extractor.set_frame_interval(interval);
+        extractor.set_delay_unit(args.delay_unit);
+        effect.apply(&mut extractor);
+        extractor.set_smoothing(args.smooth);
+        extractor.set_trails(args.trails);
+        extractor.set_noise_threshold(args.noise_threshold);
+        if let Some(seconds) = args.calibrate {
+            extractor.calibrate_noise(frames_in(seconds, interval));
+        }
+
+        // The areas given on the command line are drawn on the view of the first camera.
+        let roi = if camera == 0 {
+            args.roi(config.resolution, (width, height))
+                .expect("Failed to load ROI")
+        } else {
+            None
+        };
+        let roi_editor = RoiEditor::new((width, height), config.resolution, roi);
+        extractor.set_roi(roi_editor.roi.clone());
+
+        let sensor = (args.events.is_some() || args.clips.is_some()).then(|| EventDetector::new(args.event_config()));
+
+        // Every camera records into a directory of its own when there are several.
+        let clips = args.clips.as_ref().map(|dir| {
+            let dir = if controls.effects.len() > 1 {
+                dir.join(Path::new(&name).file_name().unwrap_or(name.as_ref()))
+            } else {
+                dir.clone()
+            };
+
+            ClipRecorder::create(dir, config.resolution, interval, args.pre_roll(), args.post_roll())
+                .expect("Failed to create clip directory")
+        });
+
+        Self {
+            name,
+            config,
+            width,
+            height,
+            interval,
+            extractor,
+            roi_editor,
+            monitor,
+            fps: FpsCounter::new(),
+            frame_rate: 0.0,
+            detector: MotionDetector::new(width, height, args.detector_config()),
+            magnitude: vec![0u8; width * height],
+            display_buf: vec![0u32; width * height],
+            overlay_buf: vec![0u32; width * height],
+            sensor,
+            clock,
+            score: 0.0,
+            clips,
+            stats: PipelineStats::new(),
+            report: None,
+            tx_close,
+            cap_handle,
+            dec_handle,
+        }
+    }
+
+    /// Extracts the motion of a decoded frame, unless the picture is frozen. Returns the motion
+    /// event that ended with it, if any.
+    pub fn process(&mut self, decoded: Decoded, controls: &Controls, args: &Args) -> Option<MotionEvent> {
+        let Decoded {
+            timestamp,
+            sequence,
+            capture_wait,
+            decode_time,
+            queued,
+            pixels: curr,
+            jpeg,
+            ..
+        } = decoded;
+        let mut event = None;
+
+        self.stats.record_frame(sequence);
+        self.stats.record_stage(Stage::Capture, capture_wait);
+        self.stats.record_stage(Stage::Decode, decode_time);
+        self.stats.record_queue(Queue::Captured, queued);
+
+        // Frames keep being received while frozen so that no latency builds up in the pipeline.
+        if !controls.frozen {
+            let diffing = Instant::now();
+
+            self.display_buf
+                .copy_from_slice(self.extractor.push_frame_at(&curr, timestamp));
+
+            if controls.detect || self.sensor.is_some() {
+                self.extractor.magnitude(&mut self.magnitude);
+            }
+
+            if controls.detect {
+                self.detector.detect(&self.magnitude);
+            }
+
+            if let Some(sensor) = &mut self.sensor {
+                let captured = self.clock.time(timestamp);
+                self.score = motion_score(&self.magnitude, args.score, args.threshold, self.extractor.roi());
+                event = sensor.update(self.score, captured);
+
+                if let Some(clips) = &mut self.clips
+                    && let Some(jpeg) = jpeg
+                {
+                    match clips.push_frame(jpeg, sensor.is_active(), captured) {
+                        Ok(Some(path)) => eprintln!("Saved motion clip {}", path.display()),
+                        Ok(None) => {}
+                        Err(err) => eprintln!("Error writing motion clip: {}", err),
+                    }
+                }
+            }
+
+            self.stats.record_stage(Stage::Diff, diffing.elapsed());
+        }
+
+        self.frame_rate = self.fps.tick();
+        event
+    }
+
+    /// The picture to show for the `camera` with this index: the motion with the overlays
+    /// `controls` ask for, or a notice while the camera is gone.
+    pub fn render(&mut self, controls: &Controls, camera: usize) -> &[u32] {
+        let (width, height) = (self.width, self.height);
+
+        if let Some(lost_for) = self.monitor.lost_for() {
+            self.overlay_buf.fill(0);
+            let mut lines = vec![
+                "NO SIGNAL".to_string(),
+                format!("RECONNECTING FOR {} S", lost_for.as_secs()),
+            ];
+            if controls.effects.len() > 1 {
+                lines.insert(0, self.label(camera));
+            }
+            hud::draw(&mut self.overlay_buf, width, height, &lines);
+            return &self.overlay_buf;
+        }
+
+        let blobs = controls.detect.then(|| self.detector.blobs());
+        let drawing = self.roi_editor.points();
+
+        if !controls.show_hud && blobs.is_none() && drawing.is_empty() {
+            return &self.display_buf;
+        }
+
+        self.overlay_buf.copy_from_slice(&self.display_buf);
+
+        if !drawing.is_empty() {
+            hud::draw_path(&mut self.overlay_buf, width, height, drawing, ROI_COLOUR);
+        }
+
+        if let Some(blobs) = blobs {
+            hud::draw_blobs(&mut self.overlay_buf, width, height, blobs, BLOB_COLOUR);
+        }
+
+        if controls.show_hud {
+            let motion = self.sensor.as_ref().map(|sensor| (self.score, sensor.is_active()));
+            let mut lines = controls.hud_lines(
+                camera,
+                self.frame_rate,
+                &self.extractor,
+                blobs.map(<[_]>::len),
+                motion,
+                self.monitor.outages(),
+            );
+            if controls.effects.len() > 1 {
+                lines.insert(0, self.label(camera));
+            }
+            if let Some(report) = &self.report {
+                lines.extend(report.hud_lines());
+            }
+            hud::draw(&mut self.overlay_buf, width, height, &lines);
+        }
+
+        &self.overlay_buf
+    }
+
+    /// Names the `camera` with this index by its hotkey and device.
+    fn label(&self, camera: usize) -> String {
+        format!("CAMERA {} {}", camera + 1, self.name)
+    }
+
+    /// Ends the reporting period of the pipeline statistics, printing them and keeping them for
+    /// the on-screen display.
+    pub fn report_stats(&mut self, several: bool) {
+        if several {
+            eprintln!("Pipeline of {}: {}", self.name, self.stats);
+        } else {
+            eprintln!("Pipeline: {}", self.stats);
+        }
+
+        self.report = Some(self.stats.clone());
+        self.stats.reset();
+    }
+
+    /// Ends the motion still going on and the clip of it, reports the outages of the camera and
+    /// asks its threads to stop. Returns the motion event cut short, if any.
+    pub fn finish(&mut self) -> Option<MotionEvent> {
+        if self.monitor.outages() > 0 {
+            eprintln!(
+                "Lost {} {} times, for {:.1} s in total",
+                self.name,
+                self.monitor.outages(),
+                self.monitor.downtime().as_secs_f64()
+            );
+        }
+
+        if let Some(clips) = &mut self.clips {
+            match clips.finish() {
+                Ok(Some(path)) => eprintln!("Saved motion clip {}", path.display()),
+                Ok(None) => {}
+                Err(err) => eprintln!("Error finishing motion clip: {}", err),
+            }
+        }
+
+        // The threads may already have stopped on their own, e.g. at the end of a file.
+        let _ = self.tx_close.send(());
+
+        // Motion still going on when the program stops is logged up to this point.
+        self.sensor.as_mut()?.finish(self.clock.now())
+    }
+
+    /// Waits for the threads to stop, after [`finish`](Self::finish).
+    pub fn join(self) {
+        self.cap_handle.join().expect("Failed to join capture thread");
+        self.dec_handle.join().expect("Failed to join decode thread");
+    }
+}
@@ -0,0 +1,42 @@
+use std::io::Cursor;
+use std::time::Duration;
+
+use motion_extraction::{AviReader, AviWriter, encode_jpeg};
+
+#[test]
+fn written_frames_read_back_unchanged() {
+    let frames: Vec<Vec<u8>> = (0..5u32)
+        .map(|n| {
+            let pixels: Vec<u32> = (0..16 * 8).map(|i| i * 0x010203 + n * 0x200000).collect();
+            let mut jpeg = Vec::new();
+            encode_jpeg(&pixels, 16, 8, 90, &mut jpeg).unwrap();
+            jpeg
+        })
+        .collect();
+
+    let mut writer = AviWriter::new(Cursor::new(Vec::new()), 16, 8, Duration::from_micros(33_333)).unwrap();
+    for frame in &frames {
+        writer.write_jpeg(frame).unwrap();
+    }
+    // An odd sized chunk has to be padded without corrupting the next one.
+    writer.write_jpeg(&[0xFF, 0xD8, 0xFF]).unwrap();
+
+    let mut file = writer.finish().unwrap();
+    file.set_position(0);
+
+    let mut reader = AviReader::new(file).unwrap();
+    assert_eq!((reader.width(), reader.height()), (16, 8));
+    assert_eq!(reader.frame_interval(), Duration::from_micros(33_333));
+
+    for frame in &frames {
+        assert_eq!(&reader.next_frame().unwrap().unwrap(), frame);
+    }
+    assert_eq!(reader.next_frame().unwrap().unwrap(), [0xFF, 0xD8, 0xFF]);
+    assert!(reader.next_frame().unwrap().is_none());
+}
+
+#[test]
+fn rejects_other_riff_files() {
+    let wave = b"RIFF\x04\x00\x00\x00WAVE".to_vec();
+    assert!(AviReader::new(Cursor::new(wave)).is_err());
+}
@@ -0,0 +1,52 @@
+use motion_extraction::{Background, BackgroundModel, ChannelDelays, MotionExtractor};
+
+#[test]
+fn running_average_learns_slowly() {
+    let mut model = BackgroundModel::new(Background::Average(0.5));
+    assert_eq!(model.update(&[0x000000]), [0x000000]);
+
+    // Every frame is compared against the background from before it.
+    assert_eq!(model.update(&[0x808080]), [0x000000]);
+    assert_eq!(model.update(&[0x808080]), [0x404040]);
+    assert_eq!(model.update(&[0x808080]), [0x606060]);
+}
+
+#[test]
+fn mixture_learns_a_flickering_background() {
+    let mut model = BackgroundModel::new(Background::Mixture(0.1));
+    for i in 0..100 {
+        model.update(&[if i % 2 == 0 { 0x202020 } else { 0xC0C0C0 }]);
+    }
+
+    // Both colours are background and compared against themselves, anything else against the
+    // most likely one.
+    assert_eq!(model.update(&[0x202020]), [0x202020]);
+    assert_eq!(model.update(&[0xC0C0C0]), [0xC0C0C0]);
+    let reference = model.update(&[0x00FF00])[0];
+    assert!(reference == 0x202020 || reference == 0xC0C0C0);
+}
+
+#[test]
+fn extractor_keeps_slow_objects_against_the_background() {
+    let mut extractor = MotionExtractor::new(4, 1, ChannelDelays::new(1, 1, 1));
+    extractor.set_background(Some("average:0.01".parse().unwrap()));
+    extractor.push_frame(&[0x000000; 4]);
+
+    // An object that stops is still there after the frame it arrived in, barely faded.
+    extractor.push_frame(&[0x000000, 0x000000, 0xFFFFFF, 0x000000]);
+    let output = extractor.push_frame(&[0x000000, 0x000000, 0xFFFFFF, 0x000000]);
+    assert_eq!(output, [0x000000, 0x000000, 0xFCFCFC, 0x000000]);
+
+    // Frame delays are used again without a background.
+    extractor.set_background(None);
+    let output = extractor.push_frame(&[0x000000, 0x000000, 0xFFFFFF, 0x000000]);
+    assert_eq!(output, [0x000000; 4]);
+}
+
+#[test]
+fn parses_background_models() {
+    assert_eq!("average".parse(), Ok(Background::Average(0.01)));
+    assert_eq!("MOG:0.05".parse(), Ok(Background::Mixture(0.05)));
+    assert!("average:0".parse::<Background>().is_err());
+    assert!("median".parse::<Background>().is_err());
+}
@@ -0,0 +1,70 @@
+use motion_extraction::{AutoControl, CameraControl, ControlKind, PowerLine};
+
+fn control(name: &str, kind: ControlKind) -> CameraControl {
+    CameraControl {
+        id: 0x009a0901,
+        name: name.to_string(),
+        kind,
+        value: Some(3),
+        default: Some(3),
+        read_only: false,
+        inactive: false,
+    }
+}
+
+#[test]
+fn controls_are_named_like_v4l2_ctl_names_them() {
+    let control = control("Auto Exposure", ControlKind::Boolean);
+    assert_eq!(control.key(), "auto_exposure");
+    assert!(control.is_named("auto_exposure"));
+    assert!(control.is_named("auto exposure"));
+    assert!(!control.is_named("exposure"));
+
+    let control = self::control("White Balance Temperature, Auto", ControlKind::Boolean);
+    assert_eq!(control.key(), "white_balance_temperature_auto");
+}
+
+#[test]
+fn values_are_parsed_by_kind() {
+    let integer = control(
+        "Gain",
+        ControlKind::Integer {
+            minimum: 0,
+            maximum: 255,
+            step: 1,
+        },
+    );
+    assert_eq!(integer.parse_value("128"), Ok(128));
+    assert!(integer.parse_value("256").is_err());
+    assert!(integer.parse_value("high").is_err());
+
+    let boolean = control("Focus, Auto", ControlKind::Boolean);
+    assert_eq!(boolean.parse_value("false"), Ok(0));
+    assert_eq!(boolean.parse_value("1"), Ok(1));
+    assert!(boolean.parse_value("2").is_err());
+
+    let menu = control(
+        "Auto Exposure",
+        ControlKind::Menu(vec![
+            (1, "Manual Mode".to_string()),
+            (3, "Aperture Priority Mode".to_string()),
+        ]),
+    );
+    assert_eq!(menu.parse_value("manual mode"), Ok(1));
+    assert_eq!(menu.parse_value("3"), Ok(3));
+    assert!(menu.parse_value("2").is_err());
+    assert_eq!(menu.format_value(3), "3 (Aperture Priority Mode)");
+
+    assert!(control("Reset", ControlKind::Other).parse_value("1").is_err());
+}
+
+#[test]
+fn parses_locks_and_power_line_frequencies() {
+    assert_eq!("wb".parse(), Ok(AutoControl::WhiteBalance));
+    assert_eq!("Exposure".parse(), Ok(AutoControl::Exposure));
+    assert!("iso".parse::<AutoControl>().is_err());
+
+    assert_eq!("50Hz".parse(), Ok(PowerLine::Hz50));
+    assert_eq!("off".parse(), Ok(PowerLine::Off));
+    assert!("55".parse::<PowerLine>().is_err());
+}
@@ -0,0 +1,25 @@
+use std::process::Command;
+
+fn run(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_motion-extraction"))
+        .args(["--pattern", "squares", "--headless"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn zero_sizes_and_frame_rates_are_rejected() {
+    for args in [["--width", "0"], ["--height", "0"], ["--fps", "0"]] {
+        let output = run(&args);
+        assert_eq!(output.status.code(), Some(2), "{:?} was accepted", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains(args[0]));
+    }
+}
+
+#[test]
+fn release_above_trigger_is_rejected() {
+    let output = run(&["--trigger", "0.1", "--release", "0.2"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--release 0.2 is above --trigger 0.1"));
+}
@@ -0,0 +1,45 @@
+use std::fs;
+use std::time::{Duration, UNIX_EPOCH};
+
+use motion_extraction::{AviReader, ClipRecorder};
+
+#[test]
+fn clips_include_pre_and_post_roll() {
+    let dir = std::env::temp_dir().join(format!("motion-clips-{}", std::process::id()));
+    let interval = Duration::from_millis(100);
+    let mut recorder = ClipRecorder::create(
+        &dir,
+        (4, 2),
+        interval,
+        Duration::from_millis(200),
+        Duration::from_millis(100),
+    )
+    .unwrap();
+
+    // Stand-ins for JPEG data, which is stored without looking at it.
+    let motion = [false, false, false, false, true, true, false, false, false];
+    let mut saved = Vec::new();
+    for (n, &motion) in motion.iter().enumerate() {
+        let time = UNIX_EPOCH + interval * n as u32;
+        if let Some(path) = recorder.push_frame(vec![n as u8; 3], motion, time).unwrap() {
+            saved.push((n, path));
+        }
+    }
+    assert!(!recorder.is_recording());
+
+    // Two frames of pre-roll, the motion and one frame of post-roll. The clip is completed with
+    // the frame after that.
+    assert_eq!(saved.len(), 1);
+    let (completed_at, path) = &saved[0];
+    assert_eq!(*completed_at, 7);
+    assert_eq!(path.file_name().unwrap(), "motion-1970-01-01T00-00-00.400Z.avi");
+
+    let mut reader = AviReader::open(path).unwrap();
+    let mut frames = Vec::new();
+    while let Some(frame) = reader.next_frame().unwrap() {
+        frames.push(frame[0]);
+    }
+    assert_eq!(frames, [2, 3, 4, 5, 6]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use motion_extraction::{Frame, PixelFormat, decode};
+
+fn decode_raw(format: PixelFormat, width: usize, height: usize, data: Vec<u8>) -> Vec<u32> {
+    let frame = Frame {
+        format,
+        width,
+        height,
+        data,
+        timestamp: Duration::ZERO,
+        sequence: 0,
+    };
+
+    let mut pixels = vec![0; width * height];
+    decode(&frame, width, height, &mut pixels).unwrap();
+    pixels
+}
+
+#[test]
+fn yuyv_uses_shared_chroma_per_pixel_pair() {
+    // Black and white luma around neutral chroma, then pure luma 128 with strong red chroma.
+    let data = vec![16, 128, 235, 128, 128, 90, 128, 240];
+    let pixels = decode_raw(PixelFormat::Yuyv, 4, 1, data);
+
+    assert_eq!(pixels[0], 0x000000);
+    assert_eq!(pixels[1], 0xFFFFFF);
+    assert_eq!(pixels[2], pixels[3]);
+    assert!(pixels[2] >> 16 > 0xF0 && pixels[2] & 0xFF < 0x80);
+}
+
+#[test]
+fn nv12_skips_row_padding() {
+    // 2x2 image with rows padded to 4 bytes, one chroma row for the whole image.
+    let data = vec![16, 235, 0, 0, 235, 16, 0, 0, 128, 128, 0, 0];
+    let pixels = decode_raw(PixelFormat::Nv12, 2, 2, data);
+
+    assert_eq!(pixels, [0x000000, 0xFFFFFF, 0xFFFFFF, 0x000000]);
+}
+
+#[test]
+fn grey_and_rgb24_are_packed_as_is() {
+    assert_eq!(
+        decode_raw(PixelFormat::Grey, 2, 1, vec![0x12, 0xAB]),
+        [0x121212, 0xABABAB]
+    );
+    assert_eq!(decode_raw(PixelFormat::Rgb24, 1, 1, vec![1, 2, 3]), [0x010203]);
+}
+
+#[test]
+fn truncated_raw_frames_are_rejected() {
+    let frame = Frame {
+        format: PixelFormat::Yuyv,
+        width: 4,
+        height: 2,
+        data: vec![0; 12],
+        timestamp: Duration::ZERO,
+        sequence: 0,
+    };
+
+    assert!(decode(&frame, 4, 2, &mut [0; 8]).is_err());
+}
@@ -0,0 +1,74 @@
+use motion_extraction::{ChannelDelays, DetectorConfig, MotionDetector, MotionExtractor};
+
+const WIDTH: usize = 16;
+const HEIGHT: usize = 12;
+
+/// Magnitudes with a filled rectangle of motion and a single noisy pixel.
+fn magnitude() -> Vec<u8> {
+    let mut magnitude = vec![0; WIDTH * HEIGHT];
+    for y in 2..6 {
+        for x in 3..9 {
+            magnitude[y * WIDTH + x] = 200;
+        }
+    }
+    magnitude[10 * WIDTH + 14] = 255;
+    magnitude
+}
+
+#[test]
+fn labels_connected_components() {
+    let mut detector = MotionDetector::new(
+        WIDTH,
+        HEIGHT,
+        DetectorConfig {
+            threshold: 100,
+            erode: 0,
+            dilate: 0,
+            min_area: 1,
+        },
+    );
+
+    let blobs = detector.detect(&magnitude()).to_vec();
+    assert_eq!(blobs.len(), 2);
+
+    let blob = blobs[0];
+    assert_eq!((blob.x, blob.y, blob.width, blob.height, blob.area), (3, 2, 6, 4, 24));
+    assert_eq!(blob.centroid, (5.5, 3.5));
+
+    assert_eq!((blobs[1].x, blobs[1].y, blobs[1].area), (14, 10, 1));
+    assert_eq!(detector.mask().iter().filter(|&&m| m != 0).count(), 25);
+}
+
+#[test]
+fn erosion_and_min_area_drop_noise() {
+    let mut detector = MotionDetector::new(WIDTH, HEIGHT, DetectorConfig::default());
+
+    // One erosion and two dilations grow the rectangle by a pixel on every side, the noisy pixel
+    // does not survive the erosion.
+    let blobs = detector.detect(&magnitude());
+    assert_eq!(blobs.len(), 1);
+    assert_eq!((blobs[0].x, blobs[0].y, blobs[0].width, blobs[0].height), (2, 1, 8, 6));
+
+    let mut detector = MotionDetector::new(
+        WIDTH,
+        HEIGHT,
+        DetectorConfig {
+            min_area: 100,
+            ..DetectorConfig::default()
+        },
+    );
+    assert!(detector.detect(&magnitude()).is_empty());
+}
+
+#[test]
+fn magnitude_ignores_the_diff_mode() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::new(1, 1, 1));
+    extractor.push_frame(&[0x80FF00]);
+
+    // The saturating output hides the darkened red and green channels, the magnitude does not.
+    assert_eq!(extractor.push_frame(&[0x40F010]), [0x000010]);
+
+    let mut magnitude = [0];
+    extractor.magnitude(&mut magnitude);
+    assert_eq!(magnitude, [0x40]);
+}
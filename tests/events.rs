@@ -0,0 +1,126 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use motion_extraction::{EventConfig, EventDetector, EventLog, FrameClock, MotionEvent, ScoreKind, motion_score};
+
+/// 2024-05-01T12:00:00Z.
+fn at(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_714_564_800) + Duration::from_millis(millis)
+}
+
+fn detector() -> EventDetector {
+    EventDetector::new(EventConfig {
+        trigger: 0.1,
+        release: 0.05,
+        min_duration: Duration::from_millis(100),
+        release_after: Duration::from_millis(300),
+    })
+}
+
+#[test]
+fn scores_changed_pixels_and_energy() {
+    let magnitude = [0, 10, 40, 255];
+
+    assert_eq!(motion_score(&magnitude, ScoreKind::Changed, 32, None), 0.5);
+    assert_eq!(
+        motion_score(&magnitude, ScoreKind::Energy, 32, None),
+        (305.0 / 255.0) / 4.0
+    );
+}
+
+#[test]
+fn short_bursts_do_not_start_events() {
+    let mut detector = detector();
+
+    detector.update(0.5, at(0));
+    detector.update(0.5, at(50));
+    detector.update(0.0, at(100));
+    detector.update(0.5, at(150));
+
+    assert!(!detector.is_active());
+}
+
+#[test]
+fn events_end_after_staying_below_the_release_threshold() {
+    let mut detector = detector();
+
+    detector.update(0.2, at(0));
+    assert_eq!(detector.update(0.3, at(100)), None);
+    assert!(detector.is_active());
+
+    // Between the thresholds the event goes on, and a short dip below the release threshold
+    // does not end it either.
+    assert_eq!(detector.update(0.07, at(200)), None);
+    assert_eq!(detector.update(0.01, at(300)), None);
+    assert_eq!(detector.update(0.6, at(400)), None);
+    assert_eq!(detector.update(0.01, at(500)), None);
+    assert_eq!(detector.update(0.01, at(700)), None);
+
+    let event = detector.update(0.01, at(800)).expect("Event ends");
+    assert_eq!(
+        event,
+        MotionEvent {
+            start: at(0),
+            end: at(500),
+            peak: 0.6,
+        }
+    );
+    assert!(!detector.is_active());
+}
+
+#[test]
+fn footage_read_faster_than_real_time_keeps_its_own_time() {
+    let mut detector = detector();
+    let mut clock = FrameClock::footage(at(0));
+
+    // Frames 40 ms apart arrive all at once, with motion in the first ten.
+    let mut ended = None;
+    for frame in 0..30u32 {
+        let score = if frame < 10 { 0.5 } else { 0.0 };
+        ended = ended.or(detector.update(score, clock.time(Duration::from_millis(1000 + 40 * frame as u64))));
+    }
+
+    assert_eq!(
+        ended,
+        Some(MotionEvent {
+            start: at(0),
+            end: at(400),
+            peak: 0.5,
+        })
+    );
+    assert_eq!(clock.now(), at(1160));
+}
+
+#[test]
+fn events_are_logged_as_json_lines() {
+    let mut log = EventLog::new(Vec::new());
+    log.write(&MotionEvent {
+        start: at(0),
+        end: at(2500),
+        peak: 0.042,
+    })
+    .unwrap();
+
+    assert_eq!(
+        String::from_utf8(log.into_inner()).unwrap(),
+        "{\"start\":\"2024-05-01T12:00:00.000Z\",\"end\":\"2024-05-01T12:00:02.500Z\",\"duration\":2.500,\"peak\":0.0420}\n"
+    );
+}
+
+#[test]
+fn events_of_several_cameras_name_their_source() {
+    let mut log = EventLog::new(Vec::new());
+    log.write_from(
+        &MotionEvent {
+            start: at(0),
+            end: at(1000),
+            peak: 0.5,
+        },
+        "/dev/video\"2\"",
+    )
+    .unwrap();
+
+    assert_eq!(
+        String::from_utf8(log.into_inner()).unwrap(),
+        "{\"source\":\"/dev/video\\\"2\\\"\",\"start\":\"2024-05-01T12:00:00.000Z\",\"end\":\"2024-05-01T12:00:01.000Z\",\"duration\":1.000,\"peak\":0.5000}\n"
+    );
+}
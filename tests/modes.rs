@@ -0,0 +1,61 @@
+use motion_extraction::{CaptureConfig, CaptureMode, closest_mode};
+
+fn mode(format: &[u8; 4], resolution: (usize, usize), fps: &[u32]) -> CaptureMode {
+    CaptureMode {
+        format: *format,
+        resolution,
+        intervals: fps.iter().map(|&fps| (1, fps)).collect(),
+    }
+}
+
+fn request(format: Option<&[u8; 4]>, resolution: (usize, usize), fps: u32) -> CaptureConfig {
+    CaptureConfig {
+        resolution,
+        interval: (1, fps),
+        format: format.copied(),
+    }
+}
+
+#[test]
+fn prefers_nearest_resolution_then_frame_rate() {
+    let modes = [
+        mode(b"MJPG", (640, 480), &[30]),
+        mode(b"MJPG", (1280, 720), &[10, 25]),
+        mode(b"MJPG", (1920, 1080), &[30]),
+    ];
+
+    let chosen = closest_mode(&modes, &request(None, (1280, 800), 30)).unwrap();
+    assert_eq!(chosen, request(Some(b"MJPG"), (1280, 720), 25));
+}
+
+#[test]
+fn prefers_raw_formats_for_equally_close_modes() {
+    let modes = [mode(b"MJPG", (640, 480), &[30]), mode(b"YUYV", (640, 480), &[30])];
+
+    let chosen = closest_mode(&modes, &request(None, (640, 480), 30)).unwrap();
+    assert_eq!(chosen.format, Some(*b"YUYV"));
+}
+
+#[test]
+fn keeps_requested_format_if_offered() {
+    let modes = [mode(b"YUYV", (1280, 720), &[30]), mode(b"MJPG", (640, 480), &[15])];
+
+    let chosen = closest_mode(&modes, &request(Some(b"MJPG"), (1280, 720), 30)).unwrap();
+    assert_eq!(chosen, request(Some(b"MJPG"), (640, 480), 15));
+
+    let chosen = closest_mode(&modes, &request(Some(b"NV12"), (1280, 720), 30)).unwrap();
+    assert_eq!(chosen.format, Some(*b"YUYV"));
+}
+
+#[test]
+fn skips_formats_that_can_not_be_decoded() {
+    let modes = [mode(b"H264", (1280, 720), &[30])];
+    assert!(closest_mode(&modes, &request(None, (1280, 720), 30)).is_none());
+
+    // Without enumerated intervals the requested one is kept.
+    let modes = [mode(b"GREY", (320, 240), &[])];
+    assert_eq!(
+        closest_mode(&modes, &request(None, (320, 240), 24)).unwrap().interval,
+        (1, 24)
+    );
+}
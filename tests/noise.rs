@@ -0,0 +1,79 @@
+use motion_extraction::{ChannelDelays, ChannelModes, DiffMode, MotionExtractor, Smoothing, TemporalFilter};
+
+#[test]
+fn noise_threshold_suppresses_small_changes() {
+    let mut extractor = MotionExtractor::new(2, 1, ChannelDelays::new(1, 1, 1));
+    extractor.set_noise_threshold(Some(8));
+    extractor.push_frame(&[0x404040, 0x404040]);
+
+    // Changes of 7 are noise, changes of 8 are not.
+    assert_eq!(extractor.push_frame(&[0x474747, 0x484848]), [0x000000, 0x080808]);
+
+    // Suppressed channels read as unchanged in signed mode too, i.e. mid-grey.
+    extractor.set_modes(ChannelModes::all(DiffMode::Signed));
+    assert_eq!(extractor.push_frame(&[0x4E4E4E, 0x484848]), [0x808080, 0x808080]);
+}
+
+#[test]
+fn calibration_learns_the_noise_of_every_pixel() {
+    let mut extractor = MotionExtractor::new(2, 1, ChannelDelays::new(1, 1, 1));
+    extractor.push_frame(&[0x404040, 0x404040]);
+
+    extractor.calibrate_noise(2);
+    extractor.push_frame(&[0x434040, 0x404040]);
+    assert!(extractor.is_calibrating());
+    extractor.push_frame(&[0x404040, 0x404041]);
+    assert!(!extractor.is_calibrating());
+    assert_eq!(extractor.noise_floor(), Some(&[4, 2][..]));
+
+    assert_eq!(extractor.push_frame(&[0x434040, 0x404043]), [0x000000, 0x000002]);
+}
+
+#[test]
+fn calibration_waits_for_the_history_to_fill() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::new(1, 1, 2));
+    extractor.calibrate_noise(1);
+
+    // The first two frames are compared against the blank history and are not measured.
+    extractor.push_frame(&[0x808080]);
+    extractor.push_frame(&[0x808080]);
+    assert!(extractor.is_calibrating());
+    extractor.push_frame(&[0x828080]);
+    assert!(!extractor.is_calibrating());
+    assert_eq!(extractor.noise_floor(), Some(&[3][..]));
+
+    assert_eq!(extractor.push_frame(&[0xE48080]), [0x620000]);
+}
+
+#[test]
+fn ema_and_box_smoothing_average_frames() {
+    let mut ema = TemporalFilter::new(Smoothing::Ema(0.5));
+    let mut box_filter = TemporalFilter::new("box:2".parse().unwrap());
+
+    let frames = [0x000000, 0x804020, 0x804020, 0x000000];
+    let averaged = frames.map(|pixel| {
+        let (mut a, mut b) = ([pixel], [pixel]);
+        ema.apply(&mut a);
+        box_filter.apply(&mut b);
+        (a[0], b[0])
+    });
+
+    assert_eq!(
+        averaged,
+        [
+            (0x000000, 0x000000),
+            (0x402010, 0x402010),
+            (0x603018, 0x804020),
+            (0x30180C, 0x402010)
+        ]
+    );
+}
+
+#[test]
+fn smoothing_is_parsed() {
+    assert_eq!("off".parse(), Ok(Smoothing::Off));
+    assert_eq!("EMA:0.25".parse(), Ok(Smoothing::Ema(0.25)));
+    assert!("ema:0".parse::<Smoothing>().is_err());
+    assert!("box:0".parse::<Smoothing>().is_err());
+    assert!("median:3".parse::<Smoothing>().is_err());
+}
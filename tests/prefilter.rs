@@ -0,0 +1,66 @@
+use motion_extraction::{Prefilter, SpatialFilter};
+
+#[test]
+fn downscale_averages_blocks() {
+    let mut filter = SpatialFilter::new(Prefilter::Downscale(2), (4, 2));
+    assert_eq!(filter.output_size(), (2, 1));
+
+    let frame = [
+        0x000000, 0x040404, 0xFF0000, 0xFF0000, //
+        0x080808, 0x0C0C0C, 0xFF0000, 0xFF0000,
+    ];
+    let mut out = [0; 2];
+    filter.apply(&frame, &mut out);
+    assert_eq!(out, [0x060606, 0xFF0000]);
+}
+
+#[test]
+fn downscale_averages_frames_smaller_than_the_factor() {
+    let mut filter = SpatialFilter::new(Prefilter::Downscale(4), (2, 1));
+    assert_eq!(filter.output_size(), (1, 1));
+
+    let frame = [0x000000, 0x080808];
+    let mut out = [0; 1];
+    filter.apply(&frame, &mut out);
+    assert_eq!(out, [0x040404]);
+}
+
+#[test]
+fn box_blur_repeats_the_edges() {
+    let mut filter = SpatialFilter::new(Prefilter::Box(1), (3, 1));
+    let mut out = [0; 3];
+
+    filter.apply(&[0x000000, 0x090909, 0x000000], &mut out);
+    assert_eq!(out, [0x030303, 0x030303, 0x030303]);
+
+    // Flat areas stay as they are.
+    filter.apply(&[0x102030; 3], &mut out);
+    assert_eq!(out, [0x102030; 3]);
+}
+
+#[test]
+fn gaussian_blur_spreads_a_point_evenly() {
+    let mut filter = SpatialFilter::new(Prefilter::Gaussian(1.0), (7, 7));
+    let mut frame = [0; 49];
+    frame[24] = 0xFFFFFF;
+    let mut out = [0; 49];
+    filter.apply(&frame, &mut out);
+
+    let at = |x: usize, y: usize| out[y * 7 + x] & 0xFF;
+    assert!(at(3, 3) > at(2, 3) && at(2, 3) > at(1, 3) && at(1, 3) > 0);
+    assert_eq!(at(2, 3), at(4, 3));
+    assert_eq!(at(2, 3), at(3, 2));
+    assert_eq!(at(1, 1), at(5, 5));
+}
+
+#[test]
+fn parses_prefilters() {
+    assert_eq!("off".parse(), Ok(Prefilter::Off));
+    assert_eq!("gaussian:1.5".parse(), Ok(Prefilter::Gaussian(1.5)));
+    assert_eq!("Box:2".parse(), Ok(Prefilter::Box(2)));
+    assert_eq!("downscale:4".parse(), Ok(Prefilter::Downscale(4)));
+    assert!("gaussian:0".parse::<Prefilter>().is_err());
+    assert!("downscale:17".parse::<Prefilter>().is_err());
+    assert!("median:3".parse::<Prefilter>().is_err());
+    assert_eq!(Prefilter::Downscale(4).output_size((1280, 720)), (320, 180));
+}
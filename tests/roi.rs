@@ -0,0 +1,77 @@
+use motion_extraction::{
+    ChannelDelays, ChannelModes, Colormap, DiffMode, MotionExtractor, MotionMode, RoiMask, ScoreKind, motion_score,
+    parse_polygons,
+};
+
+#[test]
+fn polygons_cover_the_pixels_whose_centre_they_contain() {
+    let polygons = parse_polygons("# a square and a triangle\n1,1 4,1 4,3 1,3\n\n6,0 8,0 6,2\n").unwrap();
+    let roi = RoiMask::from_polygons(&polygons, 8, 4);
+
+    #[rustfmt::skip]
+    assert_eq!(roi.as_slice(), [
+        0, 0, 0, 0, 0, 0, 1, 0,
+        0, 1, 1, 1, 0, 0, 0, 0,
+        0, 1, 1, 1, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    ]);
+    assert_eq!(roi.area(), 7);
+}
+
+#[test]
+fn malformed_polygons_are_rejected() {
+    assert!(parse_polygons("1,1 2,2").is_err());
+    assert!(parse_polygons("1,1 2;2 3,3").is_err());
+}
+
+#[test]
+fn motion_outside_the_region_is_ignored() {
+    let mut roi = RoiMask::full(4, 1);
+    roi.exclude_polygon(&[(2.0, 0.0), (4.0, 0.0), (4.0, 1.0), (2.0, 1.0)]);
+
+    let mut extractor = MotionExtractor::new(4, 1, ChannelDelays::new(1, 1, 1));
+    extractor.set_roi(Some(roi));
+    extractor.push_frame(&[0; 4]);
+    assert_eq!(
+        extractor.push_frame(&[0xFFFFFF, 0, 0xFFFFFF, 0xFFFFFF]),
+        [0xFFFFFF, 0, 0, 0]
+    );
+
+    // Half of the pixels inside the region changed.
+    let mut magnitude = [0; 4];
+    extractor.magnitude(&mut magnitude);
+    assert_eq!(magnitude, [255, 0, 0, 0]);
+    assert_eq!(motion_score(&magnitude, ScoreKind::Changed, 32, extractor.roi()), 0.5);
+}
+
+#[test]
+fn signed_channels_are_mid_grey_outside_the_region() {
+    let mut roi = RoiMask::full(2, 1);
+    roi.exclude_polygon(&[(1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0)]);
+
+    let mut extractor = MotionExtractor::new(2, 1, ChannelDelays::new(1, 1, 1));
+    extractor.set_modes(ChannelModes::new(
+        DiffMode::Signed,
+        DiffMode::Absolute,
+        DiffMode::Signed,
+    ));
+    extractor.set_roi(Some(roi));
+    extractor.push_frame(&[0; 2]);
+    assert_eq!(extractor.push_frame(&[0; 2])[1], 0x800080);
+}
+
+#[test]
+fn luma_outside_the_region_looks_like_still_pixels() {
+    let mut roi = RoiMask::full(2, 1);
+    roi.exclude_polygon(&[(1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0)]);
+
+    let mut extractor = MotionExtractor::new(2, 1, ChannelDelays::new(1, 1, 1));
+    extractor.set_mode(MotionMode::Luma {
+        delay: 1,
+        colormap: Colormap::Viridis,
+    });
+    extractor.set_roi(Some(roi));
+    extractor.push_frame(&[0; 2]);
+    let still = Colormap::Viridis.palette()[0];
+    assert_eq!(extractor.push_frame(&[0; 2]), [still, still]);
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use motion_extraction::{PipelineStats, Queue, Stage};
+
+fn millis(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn sequence_gaps_count_as_dropped_frames() {
+    let mut stats = PipelineStats::new();
+    for sequence in [0, 1, 2, 5, 6, 9] {
+        stats.record_frame(sequence);
+    }
+    assert_eq!((stats.frames(), stats.dropped()), (6, 4));
+
+    // Gaps across periods count, numbers starting over do not.
+    stats.reset();
+    stats.record_frame(11);
+    stats.record_frame(0);
+    stats.record_frame(1);
+    assert_eq!((stats.frames(), stats.dropped()), (3, 1));
+}
+
+#[test]
+fn stages_queues_and_latency_are_averaged() {
+    let mut stats = PipelineStats::new();
+    assert_eq!(stats.stage(Stage::Decode), None);
+
+    stats.record_stage(Stage::Decode, millis(4));
+    stats.record_stage(Stage::Decode, millis(8));
+    stats.record_queue(Queue::Captured, 0);
+    stats.record_queue(Queue::Captured, 3);
+    stats.record_latency(millis(50));
+
+    assert_eq!(stats.stage(Stage::Decode), Some((millis(6), millis(8))));
+    assert_eq!(stats.queue(Queue::Captured), Some((1.5, 3)));
+    assert_eq!(stats.queue(Queue::Decoded), None);
+    assert_eq!(stats.latency(), Some((millis(50), millis(50))));
+
+    stats.record_frame(0);
+    assert_eq!(
+        stats.to_string(),
+        "1 frames, 0 dropped, latency 50.0 ms (max 50.0), decode 6.0 ms (max 8.0), captured queue 1.5 (max 3)"
+    );
+    assert_eq!(
+        stats.hud_lines(),
+        ["DECODE 6.0 MS", "QUEUED 1.5/3 - DROPPED 0", "LATENCY 50 MS"]
+    );
+}
@@ -0,0 +1,178 @@
+use motion_extraction::{
+    CaptureConfig, ChannelDelays, ChannelModes, Colormap, DiffMode, FrameSource, MotionExtractor, MotionMode, Pattern,
+    SyntheticSource, decode,
+};
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+
+fn open(pattern: Pattern, seed: u64) -> SyntheticSource {
+    let mut source = SyntheticSource::open(pattern, seed);
+    source
+        .configure(&CaptureConfig {
+            resolution: (WIDTH, HEIGHT),
+            interval: (1, 30),
+            format: None,
+        })
+        .unwrap();
+
+    source
+}
+
+/// Decodes the next `count` frames the way the binary's decode thread does.
+fn frames(source: &mut SyntheticSource, count: usize) -> Vec<Vec<u32>> {
+    (0..count)
+        .map(|_| {
+            let frame = source.next_frame().unwrap().unwrap();
+            let mut pixels = vec![0; WIDTH * HEIGHT];
+            decode(&frame, WIDTH, HEIGHT, &mut pixels).unwrap();
+            pixels
+        })
+        .collect()
+}
+
+#[test]
+fn extractor_diffs_each_channel_against_its_own_delay() {
+    let mut extractor = MotionExtractor::new(2, 1, ChannelDelays::new(1, 2, 0));
+
+    extractor.push_frame(&[0x102030, 0xFFFFFF]);
+    extractor.push_frame(&[0x405060, 0x000000]);
+    let diff = extractor.push_frame(&[0x807060, 0x808080]);
+
+    // Red against the previous frame, green against the first one, blue against itself.
+    assert_eq!(diff, [0x405000, 0x800000]);
+}
+
+#[test]
+fn history_starts_out_black() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::default());
+
+    assert_eq!(extractor.push_frame(&[0x123456]), [0x123456]);
+    assert_eq!(extractor.push_frame(&[0x123456]), [0x003456]);
+}
+
+#[test]
+fn negative_delays_hold_the_output_back() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::new(-1, 0, 1));
+    assert_eq!(extractor.delays().latency(), 1);
+
+    extractor.push_frame(&[0x101010]);
+    extractor.push_frame(&[0x202020]);
+    let diff = extractor.push_frame(&[0x050505]);
+
+    // The output is the second frame: red against the third, blue against the first.
+    assert_eq!(diff, [0x1B0010]);
+}
+
+#[test]
+fn changing_delays_keeps_the_recent_history() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::new(1, 1, 1));
+    extractor.push_frame(&[0x101010]);
+    extractor.push_frame(&[0x303030]);
+
+    extractor.set_delays(ChannelDelays::new(1, 2, 3));
+    let diff = extractor.push_frame(&[0x404040]);
+
+    // Green and blue reach past the old history and find copies of its oldest frame.
+    assert_eq!(diff, [0x103030]);
+
+    extractor.set_delays(ChannelDelays::new(0, 0, 1));
+    assert_eq!(extractor.push_frame(&[0x606060]), [0x000020]);
+}
+
+#[test]
+fn diff_modes_apply_per_channel() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::new(1, 1, 1));
+    extractor.set_modes(ChannelModes::new(
+        DiffMode::Saturating,
+        DiffMode::Absolute,
+        DiffMode::Signed,
+    ));
+    extractor.push_frame(&[0x805020]);
+
+    // Every channel changes by 0x40, red and green get darker and blue gets brighter.
+    assert_eq!(extractor.push_frame(&[0x401060]), [0x0040A0]);
+
+    // Signed mode keeps still channels at mid-grey, the largest changes reach black and white.
+    extractor.set_modes(ChannelModes::all(DiffMode::Signed));
+    assert_eq!(extractor.push_frame(&[0x40FF00]), [0x80F750]);
+    assert_eq!(extractor.push_frame(&[0x4000FF]), [0x8000FF]);
+}
+
+#[test]
+fn luma_motion_is_colour_mapped() {
+    let mut extractor = MotionExtractor::new(2, 1, ChannelDelays::default());
+    extractor.set_mode(MotionMode::Luma {
+        delay: 1,
+        colormap: Colormap::Grayscale,
+    });
+    extractor.push_frame(&[0xFFFFFF, 0x00FF00]);
+
+    // Still pixels map to the bottom of the colour map, white to black to the top.
+    assert_eq!(extractor.push_frame(&[0x000000, 0x00FF00]), [0xFFFFFF, 0x000000]);
+
+    extractor.set_mode(MotionMode::Luma {
+        delay: 1,
+        colormap: Colormap::Heat,
+    });
+    assert_eq!(extractor.push_frame(&[0xFFFFFF, 0x00FF00]), [0xFFFFFF, 0x000000]);
+
+    assert_eq!(Colormap::Viridis.palette()[0], 0x440154);
+    assert_eq!(Colormap::Viridis.palette()[255], 0xFDE725);
+}
+
+#[test]
+fn squares_produce_exact_channel_delay_diff() {
+    let delays = ChannelDelays::default();
+    let frames = frames(&mut open(Pattern::Squares, 0), 24);
+    let mut extractor = MotionExtractor::new(WIDTH, HEIGHT, delays);
+
+    for (n, frame) in frames.iter().enumerate() {
+        let diff = extractor.push_frame(frame);
+
+        let older = |delay: i32| match n.checked_sub(delay as usize) {
+            Some(index) => &frames[index][..],
+            None => &[0; WIDTH * HEIGHT][..],
+        };
+        let (frame_r, frame_g, frame_b) = (older(delays.red), older(delays.green), older(delays.blue));
+
+        for i in 0..WIDTH * HEIGHT {
+            let channel = |pixel: u32, shift: u32| (pixel >> shift) & 0xFF;
+            let expected = (channel(frame[i], 16).saturating_sub(channel(frame_r[i], 16)) << 16)
+                | (channel(frame[i], 8).saturating_sub(channel(frame_g[i], 8)) << 8)
+                | channel(frame[i], 0).saturating_sub(channel(frame_b[i], 0));
+
+            assert_eq!(diff[i], expected, "pixel {} of frame {}", i, n);
+        }
+    }
+}
+
+#[test]
+fn static_background_cancels_out() {
+    let frames = frames(&mut open(Pattern::Squares, 0), 12);
+    let mut extractor = MotionExtractor::new(WIDTH, HEIGHT, ChannelDelays::default());
+
+    let diff = frames
+        .iter()
+        .map(|frame| extractor.push_frame(frame).to_vec())
+        .last()
+        .unwrap();
+
+    // No square passes this pixel within the first frames.
+    let i = 20 * WIDTH + 60;
+    assert!(frames.iter().all(|frame| frame[i] == 0x202020));
+    assert_eq!(diff[i], 0);
+    assert!(diff.iter().any(|&pixel| pixel != 0));
+}
+
+#[test]
+fn patterns_are_reproducible() {
+    for pattern in [Pattern::Squares, Pattern::Bar, Pattern::Noise] {
+        assert_eq!(frames(&mut open(pattern, 7), 3), frames(&mut open(pattern, 7), 3));
+    }
+
+    assert_ne!(
+        frames(&mut open(Pattern::Noise, 1), 1),
+        frames(&mut open(Pattern::Noise, 2), 1)
+    );
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use motion_extraction::{
+    CaptureConfig, ChannelDelays, ChannelModes, DelayUnit, DiffMode, FrameSource, MotionExtractor, Pattern,
+    SyntheticSource,
+};
+
+fn at(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+fn extractor(delays: ChannelDelays, interval: Duration) -> MotionExtractor {
+    let mut extractor = MotionExtractor::new(1, 1, delays);
+    extractor.set_frame_interval(interval);
+    extractor.set_delay_unit(DelayUnit::Milliseconds);
+    extractor
+}
+
+#[test]
+fn long_delays_in_milliseconds_keep_the_history_short() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::default());
+    extractor.set_frame_interval(at(40));
+    extractor.set_delay_unit(DelayUnit::Milliseconds);
+    extractor.set_delays(ChannelDelays::new(1, 5, 10_000));
+
+    // 10 s at 25 fps are 250 frames, with room for twice the rate, not a frame per millisecond.
+    for n in 1..=1000 {
+        extractor.push_frame_at(&[0], at(40 * n));
+        assert!(extractor.history_len() <= 2 * 250 + 2);
+    }
+}
+
+#[test]
+fn dropped_frames_do_not_change_the_delay() {
+    let mut extractor = extractor(ChannelDelays::new(100, 100, 100), at(50));
+    for (millis, pixel) in [(0, 0x000000), (50, 0x101010), (100, 0x202020), (150, 0x303030)] {
+        extractor.push_frame_at(&[pixel], at(millis));
+    }
+
+    assert_eq!(extractor.push_frame_at(&[0x404040], at(200)), [0x202020]);
+
+    // The frame of 250 ms went missing, 300 ms is still compared against 200 ms.
+    assert_eq!(extractor.push_frame_at(&[0x606060], at(300)), [0x202020]);
+}
+
+#[test]
+fn delays_pick_the_closest_frame_at_any_frame_rate() {
+    let mut extractor = extractor(ChannelDelays::new(40, 40, 40), at(20));
+    for n in 0..5 {
+        extractor.push_frame_at(&[0x101010 * n], at(20 * n as u64));
+    }
+
+    // Two frames back at 50 fps, and the closest frame, 33 ms back, at 30 fps.
+    assert_eq!(extractor.push_frame_at(&[0x505050], at(100)), [0x202020]);
+    extractor.push_frame_at(&[0x606060], at(133));
+    assert_eq!(extractor.push_frame_at(&[0x707070], at(166)), [0x101010]);
+}
+
+#[test]
+fn negative_delays_hold_the_output_back_by_time() {
+    let mut extractor = extractor(ChannelDelays::new(-50, -50, -50), at(25));
+    extractor.set_modes(ChannelModes::all(DiffMode::Absolute));
+    extractor.push_frame_at(&[0x000000], at(0));
+    extractor.push_frame_at(&[0x101010], at(25));
+
+    // The output frame is the one of 0 ms, compared against the one of 50 ms.
+    assert_eq!(extractor.push_frame_at(&[0x303030], at(50)), [0x303030]);
+}
+
+#[test]
+fn synthetic_frames_are_numbered_and_stamped() {
+    let mut source = SyntheticSource::open(Pattern::Bar, 0);
+    source
+        .configure(&CaptureConfig {
+            resolution: (8, 8),
+            interval: (1, 25),
+            format: None,
+        })
+        .unwrap();
+
+    for n in 0..3 {
+        let frame = source.next_frame().unwrap().unwrap();
+        assert_eq!(frame.sequence, n);
+        assert_eq!(frame.timestamp, at(40 * n));
+    }
+}
@@ -0,0 +1,46 @@
+use motion_extraction::{ChannelDelays, ChannelModes, Colormap, DiffMode, MotionExtractor, MotionMode};
+
+#[test]
+fn trails_fade_by_the_decay() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::new(1, 1, 1));
+    extractor.set_trails(Some(0.5));
+    extractor.push_frame(&[0x000000]);
+
+    assert_eq!(extractor.push_frame(&[0x404040]), [0x404040]);
+    assert_eq!(extractor.push_frame(&[0x404040]), [0x202020]);
+    assert_eq!(extractor.push_frame(&[0x808080]), [0x505050]);
+
+    extractor.reset_trails();
+    assert_eq!(extractor.push_frame(&[0x808080]), [0x000000]);
+}
+
+#[test]
+fn signed_trails_rest_at_mid_grey() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::new(1, 1, 1));
+    extractor.set_modes(ChannelModes::all(DiffMode::Signed));
+    extractor.set_trails(Some(0.9));
+    extractor.push_frame(&[0x404040]);
+    extractor.reset_trails();
+
+    for _ in 0..10 {
+        assert_eq!(extractor.push_frame(&[0x404040]), [0x808080]);
+    }
+
+    // Darkening leaves a dark trail.
+    assert_eq!(extractor.push_frame(&[0x000000]), [0x606060]);
+    assert_eq!(extractor.push_frame(&[0x000000]), [0x636363]);
+}
+
+#[test]
+fn luma_trails_are_coloured_after_summing() {
+    let mut extractor = MotionExtractor::new(1, 1, ChannelDelays::new(1, 1, 1));
+    extractor.set_mode(MotionMode::Luma {
+        delay: 1,
+        colormap: Colormap::Grayscale,
+    });
+    extractor.set_trails(Some(0.5));
+    extractor.push_frame(&[0x000000]);
+
+    assert_eq!(extractor.push_frame(&[0x404040]), [0x404040]);
+    assert_eq!(extractor.push_frame(&[0x404040]), [0x202020]);
+}